@@ -1,10 +1,14 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
-#[derive(Default)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Player {
     pub map_level: u32,
+    /// Clock ticks spent without enemies in sight since health last regenerated naturally.
+    pub calm_turns: u32,
 }
 
 #[derive(Bundle)]
@@ -15,19 +19,189 @@ pub struct PlayerBundle {
     pub health: Health,
     pub fov: FieldOfView,
     pub damage: Damage,
+    pub speed: Speed,
+    pub energy: Energy,
+    pub experience: Experience,
+    pub status_effects: StatusEffects,
+    pub equipment: Equipment,
 }
 
 #[derive(Default)]
 pub struct Enemy;
 
-#[derive(Default)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Health {
     pub current: i32,
     pub max: i32,
 }
 
-#[derive(Default)]
-pub struct Damage(pub i32);
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Damage(pub Dice);
+
+/// Bonus to the chance of hitting, in percent.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Accuracy(pub i32);
+
+/// Malus to the chance of being hit, in percent.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Evasion(pub i32);
+
+/// Damage absorbed from each hit.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Defense(pub i32);
+
+/// Energy gained by an actor each time the clock ticks, 100 being the normal speed.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Speed(pub i32);
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self(100)
+    }
+}
+
+/// Accumulated energy, an actor can act whenever it isn't negative.
+#[derive(Default, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Energy(pub i32);
+
+impl Energy {
+    pub const ACTION_COST: i32 = 100;
+    pub const QUICK_ACTION_COST: i32 = 50;
+
+    pub fn is_ready(&self) -> bool {
+        self.0 >= 0
+    }
+}
+
+/// Lingering effect lasting a number of turns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusEffect {
+    /// Loses 1 health each turn.
+    Poison,
+    /// Moves in a random direction half of the time.
+    Confusion,
+    /// Loses its turns.
+    Stun,
+    /// Recovers 1 health each turn.
+    Regeneration,
+}
+
+impl StatusEffect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatusEffect::Poison => "Poisoned",
+            StatusEffect::Confusion => "Confused",
+            StatusEffect::Stun => "Stunned",
+            StatusEffect::Regeneration => "Regenerating",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            StatusEffect::Poison => Color::LIME_GREEN,
+            StatusEffect::Confusion => Color::PURPLE,
+            StatusEffect::Stun => Color::ORANGE,
+            StatusEffect::Regeneration => Color::GREEN,
+        }
+    }
+}
+
+/// Effects currently affecting an actor, with the number of turns they still last.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct StatusEffects(pub Vec<(StatusEffect, i32)>);
+
+impl StatusEffects {
+    /// An effect that is already active lasts for the longest of both durations.
+    pub fn add(&mut self, effect: StatusEffect, turns: i32) {
+        match self.0.iter_mut().find(|(e, _)| *e == effect) {
+            Some((_, remaining)) => *remaining = i32::max(*remaining, turns),
+            None => self.0.push((effect, turns)),
+        }
+    }
+
+    pub fn has(&self, effect: StatusEffect) -> bool {
+        self.0.iter().any(|&(e, _)| e == effect)
+    }
+}
+
+/// Status effects applied to the victims of an enemy's hits or to whoever triggers a trap.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct InflictsEffects(pub Vec<(StatusEffect, i32)>);
+
+/// Experience points gathered towards the next character level.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Experience {
+    pub current: i32,
+    pub level: i32,
+}
+
+impl Experience {
+    pub const XP_PER_LEVEL: i32 = 10;
+
+    pub fn next_level(&self) -> i32 {
+        self.level * Self::XP_PER_LEVEL
+    }
+
+    pub fn can_level_up(&self) -> bool {
+        self.current >= self.next_level()
+    }
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self {
+            current: 0,
+            level: 1,
+        }
+    }
+}
+
+/// Experience granted to the player for a kill.
+#[derive(Default, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ExperienceValue(pub i32);
+
+/// Items an enemy may drop when killed, as template names with their chance in percent.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct LootTable(pub Vec<(String, i32)>);
+
+impl LootTable {
+    /// Picks at most one item, nothing being dropped when the roll exceeds the total chances.
+    pub fn roll(&self, rng: &mut impl Rng) -> Option<&str> {
+        let mut roll = rng.gen_range(0..100);
+        self.0.iter().find_map(|(name, chance)| {
+            if roll < *chance {
+                Some(name.as_str())
+            } else {
+                roll -= chance;
+                None
+            }
+        })
+    }
+}
+
+/// Improvements the player picks from when reaching a new character level.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelUpChoice {
+    MaxHealth,
+    Damage,
+    FieldOfView,
+}
+
+impl LevelUpChoice {
+    pub const ALL: [LevelUpChoice; 3] = [
+        LevelUpChoice::MaxHealth,
+        LevelUpChoice::Damage,
+        LevelUpChoice::FieldOfView,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            LevelUpChoice::MaxHealth => "Toughness: +5 maximum health",
+            LevelUpChoice::Damage => "Strength: +1 base damage",
+            LevelUpChoice::FieldOfView => "Perception: +1 field of view radius",
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct FieldOfView {
@@ -64,9 +238,137 @@ pub struct WantsToMove {
     pub destination: Point,
 }
 
+/// Asks for the game to be written to the save file, or read back from it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WantsToSaveOrLoad {
+    Save,
+    Load,
+}
+
 pub struct WantsToAttack {
     pub attacker: Entity,
     pub victim: Entity,
+    pub kind: AttackKind,
+}
+
+/// How an attack reaches its victim.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttackKind {
+    Melee,
+    /// Fired with a equipped `RangedWeapon` from `distance` tiles away.
+    Ranged {
+        distance: i32,
+    },
+    /// Carried `item` thrown from `distance` tiles away, landing under its victim.
+    Thrown {
+        item: Entity,
+        distance: i32,
+    },
+}
+
+/// Tile aimed at while choosing the victim of a ranged attack.
+#[derive(Default)]
+pub struct Targeting {
+    pub cursor: Option<Point>,
+    /// Last map point hovered by the mouse, the cursor only follows it when it moves.
+    pub mouse: Option<Point>,
+    /// Item thrown instead of firing the equipped ranged weapon.
+    pub thrown: Option<Entity>,
+}
+
+/// Action the player keeps taking turn after turn without input, until done or interrupted.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub enum AutoAction {
+    #[default]
+    Idle,
+    /// Waits until health is full.
+    Rest,
+    /// Walks toward the closest unexplored tile until the level is fully explored.
+    Explore,
+    /// Follows a path to a clicked tile, the next step first.
+    Travel(Vec<Point>),
+}
+
+/// Question asked to the player, the next key press answering it.
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub enum Prompt {
+    #[default]
+    None,
+    /// Which inventory line to drop.
+    Drop,
+}
+
+/// Stack of carried items selected in the inventory screen.
+#[derive(Default)]
+pub struct InventoryMenu {
+    pub selected: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttackOutcome {
+    Miss,
+    Hit(i32),
+    Critical(i32),
+}
+
+/// Result of an attack, names being `None` for the player.
+#[derive(Clone, Debug)]
+pub struct CombatEvent {
+    pub attacker: Option<String>,
+    pub victim: Option<String>,
+    pub outcome: AttackOutcome,
+    pub killed: bool,
+    /// Item dropped by the killed victim.
+    pub loot: Option<String>,
+    /// Status effects inflicted on the victim.
+    pub effects: Vec<StatusEffect>,
+}
+
+impl CombatEvent {
+    pub fn message(&self) -> String {
+        let attacker = match &self.attacker {
+            Some(name) => format!("The {}", name),
+            None => "You".to_string(),
+        };
+        let victim = match &self.victim {
+            Some(name) => format!("the {}", name),
+            None => "you".to_string(),
+        };
+        let (hit, miss) = match self.attacker {
+            Some(_) => ("hits", "misses"),
+            None => ("hit", "miss"),
+        };
+
+        let mut message = match self.outcome {
+            AttackOutcome::Miss => format!("{} {} {}", attacker, miss, victim),
+            AttackOutcome::Hit(damage) => {
+                format!("{} {} {} for {}", attacker, hit, victim, damage)
+            }
+            AttackOutcome::Critical(damage) => {
+                format!(
+                    "Critical hit! {} {} {} for {}",
+                    attacker, hit, victim, damage
+                )
+            }
+        };
+        if self.killed {
+            message += match self.victim {
+                Some(_) => ", killing it",
+                None => ", killing you",
+            };
+        }
+        for effect in self.effects.iter() {
+            message += &match self.victim {
+                Some(_) => format!(". It is {}", effect.name().to_lowercase()),
+                None => format!(". You are {}", effect.name().to_lowercase()),
+            };
+        }
+        if let Some(item) = &self.loot {
+            message += &format!(". It drops a {}", item);
+        }
+
+        message
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -89,6 +391,24 @@ pub struct InfoText;
 #[derive(Default)]
 pub struct LevelText;
 
+#[derive(Default)]
+pub struct LogText;
+
+#[derive(Default)]
+pub struct LevelUpText;
+
+#[derive(Default)]
+pub struct TargetingCursor;
+
+/// Full message history, scrolled up by `scroll` lines.
+#[derive(Default)]
+pub struct LogOverlay {
+    pub scroll: usize,
+}
+
+#[derive(Default)]
+pub struct InventoryOverlay;
+
 #[derive(Default)]
 pub struct TooltipText;
 
@@ -162,6 +482,9 @@ pub struct AmuletOfYala;
 pub struct Weapon;
 
 #[derive(Default)]
+pub struct Armor;
+
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct ProvidesHealing {
     pub amount: i32,
 }
@@ -169,9 +492,67 @@ pub struct ProvidesHealing {
 #[derive(Default)]
 pub struct ProvidesDungeonMap;
 
+/// Status effects applied to the user of an item.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct ProvidesEffects(pub Vec<(StatusEffect, i32)>);
+
+/// Danger inflicting its effects on whoever steps on it, vanishing once triggered. Shown like
+/// items once in sight.
+#[derive(Default)]
+pub struct Trap;
+
+/// Weapon attacking from afar, its victim being at most `range` tiles away.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RangedWeapon {
+    pub range: i32,
+}
+
 #[derive(Clone, PartialEq)]
 pub struct Carried(pub Entity);
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    MainHand,
+    OffHand,
+    Body,
+    Ring,
+    Amulet,
+}
+
+impl EquipmentSlot {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EquipmentSlot::MainHand => "main hand",
+            EquipmentSlot::OffHand => "off hand",
+            EquipmentSlot::Body => "body",
+            EquipmentSlot::Ring => "ring",
+            EquipmentSlot::Amulet => "amulet",
+        }
+    }
+}
+
+/// Slot an item takes once equipped.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Equippable(pub EquipmentSlot);
+
+/// Carried items an actor wields or wears, at most one per slot. Only equipped items count
+/// in combat, the others stay in the backpack.
+#[derive(Default, Clone, Debug)]
+pub struct Equipment(pub HashMap<EquipmentSlot, Entity>);
+
+impl Equipment {
+    pub fn items(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.values().copied()
+    }
+
+    pub fn slot_of(&self, item: Entity) -> Option<EquipmentSlot> {
+        self.0
+            .iter()
+            .find(|(_, &equipped)| equipped == item)
+            .map(|(&slot, _)| slot)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct ActivateItem {
     pub used_by: Entity,
@@ -180,10 +561,14 @@ pub struct ActivateItem {
 
 ////////////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Default)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Name(pub String);
 
-#[derive(Default, Clone, Copy)]
+/// Flavour text shown for an item in the inventory screen.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Description(pub String);
+
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
 pub struct Render {
     pub sprite_index: usize,
     pub sprite_order: usize,
@@ -0,0 +1,142 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// Dice expression such as `1d6+1`, written as text in templates and save files.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Dice {
+    pub count: i32,
+    pub sides: i32,
+    pub bonus: i32,
+}
+
+impl Dice {
+    pub fn new(count: i32, sides: i32, bonus: i32) -> Self {
+        Self {
+            count,
+            sides,
+            bonus,
+        }
+    }
+
+    /// Always rolls the given amount.
+    pub fn flat(bonus: i32) -> Self {
+        Self::new(0, 0, bonus)
+    }
+
+    pub fn roll(&self, rng: &mut impl Rng) -> i32 {
+        (0..self.count)
+            .map(|_| rng.gen_range(1..=self.sides))
+            .sum::<i32>()
+            + self.bonus
+    }
+}
+
+impl FromStr for Dice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid dice expression [{}]", s);
+        let s = s.trim();
+
+        let (dice, bonus) = match s.rfind(['+', '-']) {
+            Some(idx) if idx > 0 => (&s[..idx], s[idx..].trim_start_matches('+')),
+            _ if s.contains('d') => (s, "0"),
+            _ => ("", s),
+        };
+        let bonus = bonus.trim().parse::<i32>().map_err(|_| invalid())?;
+
+        if dice.is_empty() {
+            return Ok(Self::flat(bonus));
+        }
+
+        let mut parts = dice.splitn(2, 'd');
+        let count = match parts.next().unwrap().trim() {
+            "" => 1,
+            count => count.parse::<i32>().map_err(|_| invalid())?,
+        };
+        let sides = parts
+            .next()
+            .ok_or_else(invalid)?
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| invalid())?;
+        if count < 0 || sides < 1 {
+            return Err(invalid());
+        }
+
+        Ok(Self::new(count, sides, bonus))
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.count, self.bonus) {
+            // Zero dice keep their sides, so that they read back the same
+            (0, bonus) if self.sides == 0 => write!(f, "{}", bonus),
+            (count, 0) => write!(f, "{}d{}", count, self.sides),
+            (count, bonus) => write!(f, "{}d{}{:+}", count, self.sides, bonus),
+        }
+    }
+}
+
+impl TryFrom<String> for Dice {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Dice> for String {
+    fn from(dice: Dice) -> Self {
+        dice.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_accepted_forms() {
+        assert_eq!("1d6+1".parse(), Ok(Dice::new(1, 6, 1)));
+        assert_eq!("d6".parse(), Ok(Dice::new(1, 6, 0)));
+        assert_eq!("2d3-1".parse(), Ok(Dice::new(2, 3, -1)));
+        assert_eq!("-1".parse(), Ok(Dice::flat(-1)));
+        assert_eq!("5".parse(), Ok(Dice::flat(5)));
+        assert_eq!(" 3d4 + 2 ".parse(), Ok(Dice::new(3, 4, 2)));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for dice in [
+            Dice::new(1, 6, 1),
+            Dice::new(1, 6, 0),
+            Dice::new(2, 3, -1),
+            Dice::flat(-1),
+            Dice::flat(0),
+            Dice::new(0, 6, 0),
+            Dice::new(0, 6, 2),
+        ]
+        .iter()
+        {
+            assert_eq!(dice.to_string().parse(), Ok(*dice));
+        }
+        assert_eq!(Dice::new(2, 3, -1).to_string(), "2d3-1");
+        assert_eq!(Dice::new(1, 6, 1).to_string(), "1d6+1");
+        assert_eq!(Dice::new(0, 6, 0).to_string(), "0d6");
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for s in ["d", "1d0", "abc", "", "1d", "2x6", "1d6+"].iter() {
+            assert!(s.parse::<Dice>().is_err(), "{} should be rejected", s);
+        }
+    }
+}
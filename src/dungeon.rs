@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// Levels the player left, keyed by depth, so they can be restored when coming back.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Dungeon {
+    pub levels: HashMap<u32, StoredLevel>,
+}
+
+/// Map and entities lying on it (monsters and items not carried by the player).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredLevel {
+    pub map_spec: MapSpec,
+    pub entities: Vec<SavedEntity>,
+}
+
+impl StoredLevel {
+    pub fn capture(world: &mut World) -> Self {
+        let map_spec = world.get_resource::<MapSpec>().unwrap().clone();
+
+        let entities = world
+            .query_filtered::<Entity, Or<(With<Enemy>, With<Trap>, (With<Item>, Without<Carried>))>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        let entities = entities
+            .into_iter()
+            .map(|entity| SavedEntity::capture(world, entity))
+            .collect();
+
+        Self { map_spec, entities }
+    }
+
+    /// Spawns the stored entities and their sprites.
+    pub fn restore(
+        &self,
+        commands: &mut Commands,
+        tilemap: &mut Tilemap,
+        font_handle: &Handle<Font>,
+    ) {
+        for saved in self.entities.iter() {
+            saved.restore(commands, tilemap, &self.map_spec, None, font_handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+
+    use super::*;
+
+    #[test]
+    fn stored_levels_restore_their_entities() {
+        let map_spec = MapSpec::new(Point::new(10, 10));
+
+        let mut world = World::default();
+        world.insert_resource(map_spec.clone());
+        let player = world
+            .spawn()
+            .insert(Player::default())
+            .insert(Point::new(1, 1))
+            .id();
+        world
+            .spawn()
+            .insert(Enemy)
+            .insert(Point::new(2, 3))
+            .insert(Name("Goblin".to_string()))
+            .insert(Health { current: 3, max: 5 })
+            .insert(StatusEffects(vec![(StatusEffect::Poison, 4)]));
+        world.spawn().insert(Trap).insert(Point::new(4, 4));
+        world.spawn().insert(Item).insert(Point::new(5, 5));
+        world.spawn().insert(Item).insert(Carried(player));
+
+        let level = StoredLevel::capture(&mut world);
+        assert_eq!(level.entities.len(), 3);
+
+        let mut world = World::default();
+        let mut tilemap = build_tilemap(Handle::default(), &level.map_spec);
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        level.restore(&mut commands, &mut tilemap, &Handle::default());
+        queue.apply(&mut world);
+
+        let (name, pos, health, effects) = world
+            .query_filtered::<(&Name, &Point, &Health, &StatusEffects), With<Enemy>>()
+            .iter(&world)
+            .next()
+            .unwrap();
+        assert_eq!(name.0, "Goblin");
+        assert_eq!(*pos, Point::new(2, 3));
+        assert_eq!((health.current, health.max), (3, 5));
+        assert!(effects.has(StatusEffect::Poison));
+
+        let trap = world
+            .query_filtered::<&Point, With<Trap>>()
+            .iter(&world)
+            .next()
+            .unwrap();
+        assert_eq!(*trap, Point::new(4, 4));
+
+        let items = world
+            .query_filtered::<&Point, (With<Item>, Without<Carried>)>()
+            .iter(&world)
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec![Point::new(5, 5)]);
+    }
+}
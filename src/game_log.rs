@@ -0,0 +1,33 @@
+use crate::prelude::*;
+
+const MAX_ENTRIES: usize = 500;
+
+pub struct LogEntry {
+    pub text: String,
+    pub color: Color,
+}
+
+/// Messages telling the player what happened, oldest first.
+#[derive(Default)]
+pub struct GameLog {
+    pub entries: Vec<LogEntry>,
+}
+
+impl GameLog {
+    pub fn add(&mut self, text: impl Into<String>, color: Color) {
+        self.entries.push(LogEntry {
+            text: text.into(),
+            color,
+        });
+
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+
+    /// At most `n` entries, ending `skip` entries before the last one.
+    pub fn window(&self, n: usize, skip: usize) -> &[LogEntry] {
+        let end = self.entries.len().saturating_sub(skip);
+        &self.entries[end.saturating_sub(n)..end]
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::VecDeque;
+
+use bevy::app::AppExit;
+
+use crate::prelude::*;
+
+const MAX_RANDOM_TURNS: usize = 1000;
+
+/// Keys fed to `player_input` in place of a keyboard.
+#[derive(Clone, Default)]
+pub struct ScriptedInput {
+    keys: VecDeque<KeyCode>,
+}
+
+impl ScriptedInput {
+    /// Parses whitespace separated key names, e.g. `Up Up Left G Key1`.
+    pub fn parse(script: &str) -> Result<Self, String> {
+        script
+            .split_whitespace()
+            .map(|name| key_code(name).ok_or_else(|| format!("Unknown key [{}]", name)))
+            .collect::<Result<VecDeque<_>, _>>()
+            .map(|keys| Self { keys })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Plays a number of games, each one replaying the script from the start. An empty script
+/// plays random moves instead.
+pub struct Simulation {
+    script: ScriptedInput,
+    remaining: ScriptedInput,
+    games: usize,
+    played: usize,
+    turns: usize,
+    map_level: u32,
+    outcome: Option<&'static str>,
+}
+
+impl Simulation {
+    pub fn new(script: ScriptedInput, games: usize) -> Self {
+        Self {
+            remaining: script.clone(),
+            script,
+            games,
+            played: 0,
+            turns: 0,
+            map_level: 0,
+            outcome: None,
+        }
+    }
+
+    /// Number of games over so far.
+    pub fn played(&self) -> usize {
+        self.played
+    }
+
+    fn next_key(&mut self, rng: &mut GameRng) -> Option<KeyCode> {
+        if !self.script.is_empty() {
+            return self.remaining.keys.pop_front();
+        }
+
+        if self.turns >= MAX_RANDOM_TURNS {
+            return None;
+        }
+        let key_code = match rng.gen_range(0..5) {
+            0 => KeyCode::Up,
+            1 => KeyCode::Down,
+            2 => KeyCode::Left,
+            3 => KeyCode::Right,
+            _ => KeyCode::G,
+        };
+        Some(key_code)
+    }
+
+    /// Reports the game that just ended, returns whether another one should be played.
+    fn end_game(&mut self, outcome: &str, seed: u64) -> bool {
+        self.played += 1;
+        println!(
+            "Game {}/{} (seed {}): {} on dungeon level {} after {} turns",
+            self.played,
+            self.games,
+            seed,
+            outcome,
+            self.map_level + 1,
+            self.turns,
+        );
+
+        self.remaining = self.script.clone();
+        self.turns = 0;
+        self.map_level = 0;
+
+        self.played < self.games
+    }
+}
+
+pub fn scripted_input(
+    mut turn_state: ResMut<State<TurnState>>,
+    mut simulation: ResMut<Simulation>,
+    mut rng: ResMut<GameRng>,
+    mut ev_keys: EventWriter<KeyboardInput>,
+    mut ev_exit: EventWriter<AppExit>,
+    player_query: Query<&Player>,
+    auto_action: Res<AutoAction>,
+) {
+    let outcome = match turn_state.current() {
+        // Scripts can also browse the inventory screen
+        // Keys would interrupt resting and other auto actions
+        TurnState::AwaitingInput if *auto_action != AutoAction::Idle => return,
+        TurnState::AwaitingInput | TurnState::Inventory => {
+            if let Ok(player) = player_query.single() {
+                simulation.map_level = player.map_level;
+            }
+
+            match simulation.next_key(&mut rng) {
+                Some(key_code) => {
+                    simulation.turns += 1;
+                    ev_keys.send(KeyboardInput {
+                        scan_code: 0,
+                        key_code: Some(key_code),
+                        state: ElementState::Pressed,
+                    });
+                }
+                None => {
+                    simulation.outcome = Some("Out of input");
+                    turn_state.set(TurnState::GameOver).unwrap();
+                }
+            }
+            return;
+        }
+        TurnState::ShowingLog => {
+            // The log overlay is part of the HUD, which headless games don't have
+            turn_state.set(TurnState::AwaitingInput).unwrap();
+            return;
+        }
+        TurnState::Targeting => {
+            // Fire at the default target, the closest one
+            ev_keys.send(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(KeyCode::Return),
+                state: ElementState::Pressed,
+            });
+            return;
+        }
+        TurnState::LevelUp => {
+            let key_code = match rng.gen_range(0..3) {
+                0 => KeyCode::Key1,
+                1 => KeyCode::Key2,
+                _ => KeyCode::Key3,
+            };
+            ev_keys.send(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key_code),
+                state: ElementState::Pressed,
+            });
+            return;
+        }
+        TurnState::GameOver => simulation.outcome.take().unwrap_or("Slain"),
+        TurnState::Victory => "Won",
+        _ => return,
+    };
+
+    if simulation.end_game(outcome, rng.seed()) {
+        turn_state.set(TurnState::AwaitingInput).unwrap();
+    } else {
+        ev_exit.send(AppExit);
+    }
+}
+
+/// Feeds `player_input` from the `Simulation` resource instead of a keyboard.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<KeyboardInput>()
+            .add_system(scripted_input.system().before("player_input"));
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+
+use serde::Deserialize;
+
+use crate::prelude::*;
+
+pub const KEYBINDINGS_FILE: &str = "assets/keybindings.ron";
+
+/// Key bindings shipped with the game, used when the assets file can't be loaded.
+const DEFAULT_KEYBINDINGS: &str = include_str!("../assets/keybindings.ron");
+
+/// What the player means by pressing a key, whatever the key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum PlayerAction {
+    MoveNorth,
+    MoveSouth,
+    MoveEast,
+    MoveWest,
+    MoveNorthEast,
+    MoveNorthWest,
+    MoveSouthEast,
+    MoveSouthWest,
+    Wait,
+    /// Waits turn after turn until health is full or an enemy shows up.
+    Rest,
+    /// Walks to unexplored places until something interesting shows up.
+    Explore,
+    PickUp,
+    /// Uses the item of the given inventory line, counting from 0.
+    UseItem(usize),
+    Drop,
+    Inventory,
+    Fire,
+    MessageLog,
+    Save,
+    Load,
+    /// Starts a new game from the victory and game over screens.
+    Restart,
+    /// Picks the numbered choice of a menu, counting from 0.
+    Choose(usize),
+    Confirm,
+    Cancel,
+    Equip,
+    Throw,
+    NextTarget,
+    ScrollUp,
+    ScrollDown,
+}
+
+impl PlayerAction {
+    /// Step taken by the movement actions.
+    pub fn delta(&self) -> Option<Point> {
+        let delta = match self {
+            PlayerAction::MoveNorth => Point::new(0, 1),
+            PlayerAction::MoveSouth => Point::new(0, -1),
+            PlayerAction::MoveEast => Point::new(1, 0),
+            PlayerAction::MoveWest => Point::new(-1, 0),
+            PlayerAction::MoveNorthEast => Point::new(1, 1),
+            PlayerAction::MoveNorthWest => Point::new(-1, 1),
+            PlayerAction::MoveSouthEast => Point::new(1, -1),
+            PlayerAction::MoveSouthWest => Point::new(-1, -1),
+            _ => return None,
+        };
+        Some(delta)
+    }
+
+    /// Actions taken while playing, the other ones being left to menus and screens.
+    pub fn in_game(&self) -> bool {
+        !matches!(
+            self,
+            PlayerAction::Restart
+                | PlayerAction::Choose(_)
+                | PlayerAction::Confirm
+                | PlayerAction::Cancel
+                | PlayerAction::Equip
+                | PlayerAction::Throw
+                | PlayerAction::NextTarget
+                | PlayerAction::ScrollUp
+                | PlayerAction::ScrollDown
+        )
+    }
+}
+
+/// Content of the key bindings file: key names bound to each action by named presets, and
+/// the presets in use.
+#[derive(Deserialize)]
+struct KeyBindings {
+    presets: Vec<String>,
+    bindings: HashMap<String, HashMap<PlayerAction, Vec<String>>>,
+}
+
+/// Actions bound to each key.
+pub struct InputMap {
+    keys: HashMap<KeyCode, Vec<PlayerAction>>,
+    /// Keys named for each action, in the order of the presets and of the bindings file.
+    names: HashMap<PlayerAction, Vec<(KeyCode, String)>>,
+}
+
+impl InputMap {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        Ok(Self::from_bindings(ron::de::from_reader(file)?))
+    }
+
+    /// Keys of a preset replace the ones bound by the presets listed before it.
+    fn from_bindings(key_bindings: KeyBindings) -> Self {
+        let mut keys = HashMap::new();
+        let mut key_names = HashMap::<PlayerAction, Vec<(KeyCode, String)>>::new();
+
+        for preset in key_bindings.presets.iter() {
+            let bindings = match key_bindings.bindings.get(preset) {
+                Some(bindings) => bindings,
+                None => {
+                    println!(
+                        "Warning: we don't know any key bindings preset named {}",
+                        preset
+                    );
+                    continue;
+                }
+            };
+
+            let mut preset_keys = HashMap::<KeyCode, Vec<PlayerAction>>::new();
+            for (&action, names) in bindings.iter() {
+                for name in names.iter() {
+                    match key_code(name) {
+                        Some(key) => {
+                            preset_keys.entry(key).or_default().push(action);
+                            key_names
+                                .entry(action)
+                                .or_default()
+                                .push((key, name.clone()));
+                        }
+                        None => println!("Warning: we don't know any key named {}", name),
+                    }
+                }
+            }
+
+            for (key, mut actions) in preset_keys.into_iter() {
+                actions.sort();
+                keys.insert(key, actions);
+            }
+        }
+
+        Self {
+            keys,
+            names: key_names,
+        }
+    }
+
+    pub fn actions(&self, key: KeyCode) -> &[PlayerAction] {
+        self.keys
+            .get(&key)
+            .map_or(&[], |actions| actions.as_slice())
+    }
+
+    /// In game action bound to `key`, menu actions being left to the menus and screens.
+    pub fn player_action(&self, key: KeyCode) -> Option<PlayerAction> {
+        self.actions(key)
+            .iter()
+            .copied()
+            .find(|action| action.in_game())
+    }
+
+    pub fn is_bound(&self, key: KeyCode, action: PlayerAction) -> bool {
+        self.actions(key).contains(&action)
+    }
+
+    /// Whether `ev` is the press of a key bound to `action`.
+    pub fn pressed(&self, ev: &KeyboardInput, action: PlayerAction) -> bool {
+        ev.state == ElementState::Pressed
+            && matches!(ev.key_code, Some(key) if self.is_bound(key, action))
+    }
+
+    /// First of the menu `actions` bound to the key pressed in `ev`.
+    pub fn menu_action(
+        &self,
+        ev: &KeyboardInput,
+        actions: &[PlayerAction],
+    ) -> Option<PlayerAction> {
+        actions
+            .iter()
+            .copied()
+            .find(|&action| self.pressed(ev, action))
+    }
+
+    /// Name of the first key bound to `action`, for help texts.
+    pub fn key_label(&self, action: PlayerAction) -> &str {
+        let name = self.names.get(&action).and_then(|names| {
+            names
+                .iter()
+                .find(|(key, _)| self.is_bound(*key, action))
+                .map(|(_, name)| name.as_str())
+        });
+        match name {
+            Some("Return") => "Enter",
+            Some(name) => name.strip_prefix("Key").unwrap_or(name),
+            None => "(unbound)",
+        }
+    }
+}
+
+impl FromWorld for InputMap {
+    fn from_world(_world: &mut World) -> Self {
+        InputMap::load(KEYBINDINGS_FILE).unwrap_or_else(|e| {
+            println!("Warning: unable to load {}: {}", KEYBINDINGS_FILE, e);
+            let key_bindings = ron::de::from_str(DEFAULT_KEYBINDINGS).unwrap();
+            InputMap::from_bindings(key_bindings)
+        })
+    }
+}
+
+/// Parses the name of a `KeyCode` variant, e.g. `Up`, `G`, `Key1` or `Numpad8`.
+pub fn key_code(name: &str) -> Option<KeyCode> {
+    let key_code = match name {
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Return" => KeyCode::Return,
+        "Escape" => KeyCode::Escape,
+        "Tab" => KeyCode::Tab,
+        "Space" => KeyCode::Space,
+        "Back" => KeyCode::Back,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Insert" => KeyCode::Insert,
+        "Delete" => KeyCode::Delete,
+        "Period" => KeyCode::Period,
+        "Comma" => KeyCode::Comma,
+        "Slash" => KeyCode::Slash,
+        "Semicolon" => KeyCode::Semicolon,
+        "Key1" => KeyCode::Key1,
+        "Key2" => KeyCode::Key2,
+        "Key3" => KeyCode::Key3,
+        "Key4" => KeyCode::Key4,
+        "Key5" => KeyCode::Key5,
+        "Key6" => KeyCode::Key6,
+        "Key7" => KeyCode::Key7,
+        "Key8" => KeyCode::Key8,
+        "Key9" => KeyCode::Key9,
+        "Key0" => KeyCode::Key0,
+        "Numpad0" => KeyCode::Numpad0,
+        "Numpad1" => KeyCode::Numpad1,
+        "Numpad2" => KeyCode::Numpad2,
+        "Numpad3" => KeyCode::Numpad3,
+        "Numpad4" => KeyCode::Numpad4,
+        "Numpad5" => KeyCode::Numpad5,
+        "Numpad6" => KeyCode::Numpad6,
+        "Numpad7" => KeyCode::Numpad7,
+        "Numpad8" => KeyCode::Numpad8,
+        "Numpad9" => KeyCode::Numpad9,
+        "NumpadEnter" => KeyCode::NumpadEnter,
+        "F1" => KeyCode::F1,
+        "F2" => KeyCode::F2,
+        "F3" => KeyCode::F3,
+        "F4" => KeyCode::F4,
+        "F5" => KeyCode::F5,
+        "F6" => KeyCode::F6,
+        "F7" => KeyCode::F7,
+        "F8" => KeyCode::F8,
+        "F9" => KeyCode::F9,
+        "F10" => KeyCode::F10,
+        "F11" => KeyCode::F11,
+        "F12" => KeyCode::F12,
+        "A" => KeyCode::A,
+        "B" => KeyCode::B,
+        "C" => KeyCode::C,
+        "D" => KeyCode::D,
+        "E" => KeyCode::E,
+        "F" => KeyCode::F,
+        "G" => KeyCode::G,
+        "H" => KeyCode::H,
+        "I" => KeyCode::I,
+        "J" => KeyCode::J,
+        "K" => KeyCode::K,
+        "L" => KeyCode::L,
+        "M" => KeyCode::M,
+        "N" => KeyCode::N,
+        "O" => KeyCode::O,
+        "P" => KeyCode::P,
+        "Q" => KeyCode::Q,
+        "R" => KeyCode::R,
+        "S" => KeyCode::S,
+        "T" => KeyCode::T,
+        "U" => KeyCode::U,
+        "V" => KeyCode::V,
+        "W" => KeyCode::W,
+        "X" => KeyCode::X,
+        "Y" => KeyCode::Y,
+        "Z" => KeyCode::Z,
+        _ => return None,
+    };
+    Some(key_code)
+}
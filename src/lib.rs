@@ -0,0 +1,47 @@
+mod components;
+mod dice;
+mod dungeon;
+mod game_log;
+mod headless;
+mod input_map;
+mod map_builder;
+mod rng;
+mod save_game;
+mod spawner;
+mod systems;
+mod turn_state;
+mod utils;
+
+pub mod prelude {
+    pub use bevy::app::{Events, ManualEventReader};
+    pub use bevy::ecs::component::Component;
+    pub use bevy::input::keyboard::KeyboardInput;
+    pub use bevy::input::ElementState;
+    pub use bevy::prelude::*;
+    pub use bevy::render::camera::{Camera, OrthographicProjection};
+    pub use bevy::text::Text2dSize;
+    pub use bevy::window::WindowResized;
+    pub use bevy_prototype_lyon::prelude::*;
+    pub use bevy_tilemap::prelude::*;
+    pub use bracket_pathfinding::prelude::*;
+    pub use lyon_tessellation::path::Path;
+    pub use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+
+    pub use crate::components::Name;
+    pub use crate::components::*;
+    pub use crate::dice::*;
+    pub use crate::dungeon::*;
+    pub use crate::game_log::*;
+    pub use crate::headless::*;
+    pub use crate::input_map::*;
+    pub use crate::map_builder::*;
+    pub use crate::rng::*;
+    pub use crate::save_game::*;
+    pub use crate::spawner::*;
+    pub use crate::systems::*;
+    pub use crate::turn_state::*;
+    pub use crate::utils::{to_cp437, Rect};
+}
+
+pub use headless::HeadlessPlugin;
+pub use systems::{HudPlugin, MapPlugin, RenderPlugin, SpawnerPlugin, TurnPlugin};
@@ -1,99 +1,81 @@
-mod components;
-mod map_builder;
-mod spawner;
-mod systems;
-mod turn_state;
-mod utils;
-
-mod prelude {
-    pub use bevy::app::Events;
-    pub use bevy::ecs::component::Component;
-    pub use bevy::input::keyboard::KeyboardInput;
-    pub use bevy::input::ElementState;
-    pub use bevy::prelude::*;
-    pub use bevy::render::camera::{Camera, OrthographicProjection};
-    pub use bevy::text::Text2dSize;
-    pub use bevy::window::WindowResized;
-    pub use bevy_prototype_lyon::prelude::*;
-    pub use bevy_tilemap::prelude::*;
-    pub use bracket_pathfinding::prelude::*;
-    pub use lyon_tessellation::path::Path;
-    pub use rand::{rngs::ThreadRng, Rng};
-
-    pub const TILEMAP_WIDTH: i32 = 80;
-    pub const TILEMAP_HEIGHT: i32 = 50;
-    pub const CAMERA_OFFSET_X: i32 = TILEMAP_WIDTH / 2;
-    pub const CAMERA_OFFSET_Y: i32 = TILEMAP_HEIGHT / 2;
-
-    pub use crate::components::Name;
-    pub use crate::components::*;
-    pub use crate::map_builder::*;
-    pub use crate::spawner::*;
-    pub use crate::systems::*;
-    pub use crate::turn_state::*;
-    pub use crate::utils::*;
+use dungeoncrawl::prelude::*;
+
+struct Options {
+    seed: Option<u64>,
+    headless: bool,
+    script: Option<String>,
+    games: usize,
+    movement: Movement,
 }
 
-use prelude::*;
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Options {
+            seed: None,
+            headless: false,
+            script: None,
+            games: 1,
+            movement: Movement::Cardinal,
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => match args.next().map(|seed| seed.parse()) {
+                    Some(Ok(seed)) => options.seed = Some(seed),
+                    _ => println!("Warning: --seed expects an unsigned integer"),
+                },
+                "--headless" => options.headless = true,
+                "--eight-way" => options.movement = Movement::EightWay,
+                "--script" => options.script = args.next(),
+                "--games" => match args.next().map(|games| games.parse()) {
+                    Some(Ok(games)) => options.games = games,
+                    _ => println!("Warning: --games expects an unsigned integer"),
+                },
+                _ => println!("Warning: unknown argument {}", arg),
+            }
+        }
+
+        options
+    }
+
+    fn game_rng(&self) -> GameRng {
+        match self.seed {
+            Some(seed) => GameRng::new(seed),
+            None => GameRng::from_entropy(),
+        }
+    }
+}
 
 fn main() {
+    let options = Options::from_args();
     let mut app = App::build();
 
-    app.insert_resource(WindowDescriptor {
-        title: "Dungeoncrawl Bevy".to_string(),
-        ..Default::default()
-    })
-    .add_plugins(DefaultPlugins)
-    .add_plugins(TilemapDefaultPlugins)
-    .add_plugin(ShapePlugin)
-    .add_startup_system(setup.system())
-    .add_state(TurnState::AwaitingInput)
-    .init_resource::<Events<WantsToMove>>()
-    .init_resource::<Events<WantsToAttack>>()
-    .init_resource::<Events<ActivateItem>>();
-
-    add_systems(&mut app);
-
-    app.run();
-}
-
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-) {
-    // Setup textures
-
-    let texture_handle = asset_server.load("dungeonfont.png");
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(32.0, 32.0), 16, 16);
-
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
-    commands.insert_resource(texture_atlas_handle.clone());
-
-    // Setup font
-
-    let font_handle: Handle<Font> = asset_server.load("BigBlue_Terminal_437TT.TTF");
-    commands.insert_resource(font_handle.clone());
-
-    // Build map
-
-    let (mut tilemap, map_builder) = make_tilemap(texture_atlas_handle.clone());
-    let MapBuilder {
-        player_start,
-        monster_spawns,
-        map_spec,
-        ..
-    } = map_builder;
-
-    commands.insert_resource(map_spec);
-
-    // Spawn entities
-
-    spawn_player(&mut commands, player_start, &mut tilemap);
-    spawn_level(0, &mut commands, &monster_spawns, &mut tilemap);
-    spawn_hud(&mut commands, font_handle.clone());
-    spawn_tilemap(&mut commands, tilemap);
-
-    spawn_camera(&mut commands, player_start);
-    spawn_end_game_screens(&mut commands, font_handle.clone());
+    app.insert_resource(options.game_rng());
+    app.insert_resource(options.movement);
+
+    if options.headless {
+        let script = match &options.script {
+            Some(path) => std::fs::read_to_string(path).expect("Failed reading script"),
+            None => String::default(),
+        };
+        let script = ScriptedInput::parse(&script).expect("Unable to parse script");
+
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(Simulation::new(script, options.games))
+            .add_plugin(HeadlessPlugin);
+    } else {
+        app.insert_resource(WindowDescriptor {
+            title: "Dungeoncrawl Bevy".to_string(),
+            ..Default::default()
+        })
+        .add_plugins(DefaultPlugins)
+        .add_plugin(RenderPlugin)
+        .add_plugin(HudPlugin);
+    }
+
+    app.add_plugin(TurnPlugin)
+        .add_plugin(MapPlugin)
+        .add_plugin(SpawnerPlugin)
+        .run();
 }
@@ -0,0 +1,140 @@
+use crate::prelude::*;
+use crate::utils::Rect;
+
+const MIN_LEAF_SIZE: i32 = 10;
+const MIN_ROOM_SIZE: i32 = 3;
+
+pub struct BspArchitect;
+
+impl super::MapArchitect for BspArchitect {
+    fn with_dimensions(&mut self, dimensions: Point, rng: &mut GameRng) -> MapBuilder {
+        let mut mb = MapBuilder {
+            map_spec: MapSpec::new(dimensions),
+            rooms: Vec::new(),
+            monster_spawns: Vec::new(),
+            prefab_spawns: Vec::new(),
+            player_start: Point::zero(),
+            amulet_start: Point::zero(),
+        };
+
+        mb.fill(TileType::Wall);
+
+        // Leaves exclude their right and bottom edges, keep a wall around the map
+        let root = Rect::with_exact(1, 1, mb.map_spec.width - 1, mb.map_spec.height - 1);
+        self.split(root, rng, &mut mb);
+
+        mb.player_start = mb.rooms[0].center();
+        mb.amulet_start = mb.find_most_distant();
+
+        for room in mb.rooms.iter().skip(1) {
+            mb.monster_spawns.push(room.center());
+        }
+
+        mb
+    }
+}
+
+impl BspArchitect {
+    /// Splits the leaf in two until it is too small, carving a room in each final leaf and
+    /// connecting both halves of every split. Returns a point of the subtree to connect to.
+    fn split(&self, leaf: Rect, rng: &mut GameRng, mb: &mut MapBuilder) -> Point {
+        let width = leaf.x2 - leaf.x1;
+        let height = leaf.y2 - leaf.y1;
+        let can_split_x = width >= MIN_LEAF_SIZE * 2;
+        let can_split_y = height >= MIN_LEAF_SIZE * 2;
+
+        let split_x = match (can_split_x, can_split_y) {
+            (false, false) => return self.carve_room(leaf, rng, mb),
+            (true, false) => true,
+            (false, true) => false,
+            // Favour cutting across the longest side to avoid long narrow leaves
+            _ if width * 4 > height * 5 => true,
+            _ if height * 4 > width * 5 => false,
+            _ => rng.gen_range(0..2) == 0,
+        };
+
+        let (first, second) = if split_x {
+            let x = leaf.x1 + rng.gen_range(MIN_LEAF_SIZE..=width - MIN_LEAF_SIZE);
+            (
+                Rect::with_exact(leaf.x1, leaf.y1, x, leaf.y2),
+                Rect::with_exact(x, leaf.y1, leaf.x2, leaf.y2),
+            )
+        } else {
+            let y = leaf.y1 + rng.gen_range(MIN_LEAF_SIZE..=height - MIN_LEAF_SIZE);
+            (
+                Rect::with_exact(leaf.x1, leaf.y1, leaf.x2, y),
+                Rect::with_exact(leaf.x1, y, leaf.x2, leaf.y2),
+            )
+        };
+
+        let prev = self.split(first, rng, mb);
+        let new = self.split(second, rng, mb);
+
+        if rng.gen_range(0..2) == 1 {
+            mb.apply_horizontal_tunnel(prev.x, new.x, prev.y);
+            mb.apply_vertical_tunnel(prev.y, new.y, new.x);
+        } else {
+            mb.apply_vertical_tunnel(prev.y, new.y, prev.x);
+            mb.apply_horizontal_tunnel(prev.x, new.x, new.y);
+        }
+
+        if rng.gen_range(0..2) == 0 {
+            prev
+        } else {
+            new
+        }
+    }
+
+    fn carve_room(&self, leaf: Rect, rng: &mut GameRng, mb: &mut MapBuilder) -> Point {
+        let width = leaf.x2 - leaf.x1;
+        let height = leaf.y2 - leaf.y1;
+
+        // Leave at least one tile of wall between the room and the leaf's edges
+        let room_width = rng.gen_range(MIN_ROOM_SIZE..=width - 2);
+        let room_height = rng.gen_range(MIN_ROOM_SIZE..=height - 2);
+        let room = Rect::with_size(
+            leaf.x1 + rng.gen_range(1..width - room_width),
+            leaf.y1 + rng.gen_range(1..height - room_height),
+            room_width - 1,
+            room_height - 1,
+        );
+
+        let map_spec = &mut mb.map_spec;
+        room.for_each(|p| {
+            let idx = map_spec.map_idx(p.x, p.y);
+            map_spec.tiles[idx] = TileType::Floor;
+        });
+        mb.rooms.push(room);
+
+        room.center()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_builder::MapArchitect;
+
+    #[test]
+    fn every_room_is_reachable_from_the_start() {
+        let mut rng = GameRng::new(1234);
+        for _ in 0..20 {
+            let mb = BspArchitect.new(&mut rng);
+            let dijkstra_map = DijkstraMap::new(
+                mb.map_spec.width,
+                mb.map_spec.height,
+                &[mb.map_spec.point2d_to_index(mb.player_start)],
+                &mb.map_spec,
+                1024.0,
+            );
+
+            assert!(mb.rooms.len() > 1);
+            for room in mb.rooms.iter() {
+                room.for_each(|pt| {
+                    let idx = mb.map_spec.point2d_to_index(pt);
+                    assert!(dijkstra_map.map[idx] < 1024.0, "{:?} is out of reach", pt);
+                });
+            }
+        }
+    }
+}
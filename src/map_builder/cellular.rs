@@ -3,14 +3,18 @@ use crate::prelude::*;
 pub struct CellularAutomataArchitect;
 
 impl super::MapArchitect for CellularAutomataArchitect {
-    fn new(&mut self, rng: &mut ThreadRng) -> MapBuilder {
+    fn dimensions(&self) -> Point {
+        Point::new(120, 80)
+    }
+
+    fn with_dimensions(&mut self, dimensions: Point, rng: &mut GameRng) -> MapBuilder {
         let mut mb = MapBuilder {
-            map_spec: MapSpec::new(),
+            map_spec: MapSpec::new(dimensions),
             rooms: Vec::new(),
             monster_spawns: Vec::new(),
+            prefab_spawns: Vec::new(),
             player_start: Point::zero(),
             amulet_start: Point::zero(),
-            theme: super::themes::DungeonTheme::new(),
         };
 
         self.random_noise_map(rng, &mut mb.map_spec);
@@ -43,7 +47,7 @@ impl CellularAutomataArchitect {
         for iy in -1..=1 {
             for ix in -1..=1 {
                 if !(ix == 0 && iy == 0)
-                    && map_spec.tiles[map_idx(x + ix, y + iy)] == TileType::Wall
+                    && map_spec.tiles[map_spec.map_idx(x + ix, y + iy)] == TileType::Wall
                 {
                     neighbors += 1;
                 }
@@ -54,10 +58,10 @@ impl CellularAutomataArchitect {
 
     fn iteration(&mut self, map_spec: &mut MapSpec) {
         let mut new_tiles = map_spec.tiles.clone();
-        for y in 1..TILEMAP_HEIGHT - 1 {
-            for x in 1..TILEMAP_WIDTH - 1 {
+        for y in 1..map_spec.height - 1 {
+            for x in 1..map_spec.width - 1 {
                 let neighbors = self.count_neighbors(x, y, map_spec);
-                let idx = map_idx(x, y);
+                let idx = map_spec.map_idx(x, y);
                 if neighbors > 4 || neighbors == 0 {
                     new_tiles[idx] = TileType::Wall;
                 } else {
@@ -69,7 +73,7 @@ impl CellularAutomataArchitect {
     }
 
     fn find_start(&self, map_spec: &MapSpec) -> Point {
-        let center = Point::new(TILEMAP_WIDTH / 2, TILEMAP_HEIGHT / 2);
+        let center = Point::new(map_spec.width / 2, map_spec.height / 2);
         let closest_point = map_spec
             .tiles
             .iter()
@@ -1,44 +1,43 @@
 use crate::prelude::*;
 
 const STAGGER_DISTANCE: usize = 400;
-const NUM_TILES: usize = (TILEMAP_WIDTH * TILEMAP_HEIGHT) as usize;
-const DESIRED_FLOOR: usize = NUM_TILES / 3;
 
 pub struct DrunkardsWalkArchitect;
 
 impl super::MapArchitect for DrunkardsWalkArchitect {
-    fn new(&mut self, rng: &mut ThreadRng) -> MapBuilder {
+    fn with_dimensions(&mut self, dimensions: Point, rng: &mut GameRng) -> MapBuilder {
         let mut mb = MapBuilder {
-            map_spec: MapSpec::new(),
+            map_spec: MapSpec::new(dimensions),
             rooms: Vec::new(),
             monster_spawns: Vec::new(),
+            prefab_spawns: Vec::new(),
             player_start: Point::zero(),
             amulet_start: Point::zero(),
-            theme: super::themes::DungeonTheme::new(),
         };
 
         mb.fill(TileType::Wall);
 
-        let center = Point::new(TILEMAP_WIDTH / 2, TILEMAP_HEIGHT / 2);
+        let center = Point::new(mb.map_spec.width / 2, mb.map_spec.height / 2);
         self.drunkard(&center, rng, &mut mb.map_spec);
 
+        let desired_floor = mb.map_spec.num_tiles() / 3;
         while mb
             .map_spec
             .tiles
             .iter()
             .filter(|&&t| t == TileType::Floor)
             .count()
-            < DESIRED_FLOOR
+            < desired_floor
         {
             let random_pos = Point::new(
-                rng.gen_range(0..TILEMAP_WIDTH),
-                rng.gen_range(0..TILEMAP_HEIGHT),
+                rng.gen_range(0..mb.map_spec.width),
+                rng.gen_range(0..mb.map_spec.height),
             );
             self.drunkard(&random_pos, rng, &mut mb.map_spec);
 
             let dijkstra_map = DijkstraMap::new(
-                TILEMAP_WIDTH,
-                TILEMAP_HEIGHT,
+                mb.map_spec.width,
+                mb.map_spec.height,
                 &vec![mb.map_spec.point2d_to_index(center)],
                 &mb.map_spec,
                 1024.0,
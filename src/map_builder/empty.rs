@@ -3,24 +3,24 @@ use crate::prelude::*;
 pub struct EmptyArchitect;
 
 impl super::MapArchitect for EmptyArchitect {
-    fn new(&mut self, rng: &mut ThreadRng) -> MapBuilder {
+    fn with_dimensions(&mut self, dimensions: Point, rng: &mut GameRng) -> MapBuilder {
         let mut mb = MapBuilder {
-            map_spec: MapSpec::new(),
+            map_spec: MapSpec::new(dimensions),
             rooms: Vec::new(),
             monster_spawns: Vec::new(),
+            prefab_spawns: Vec::new(),
             player_start: Point::zero(),
             amulet_start: Point::zero(),
-            theme: super::themes::DungeonTheme::new(),
         };
 
         mb.fill(TileType::Floor);
-        mb.player_start = Point::new(TILEMAP_WIDTH / 2, TILEMAP_HEIGHT / 2);
+        mb.player_start = Point::new(mb.map_spec.width / 2, mb.map_spec.height / 2);
         mb.amulet_start = mb.find_most_distant();
 
         for _ in 0..MapBuilder::NUM_MONSTERS {
             mb.monster_spawns.push(Point::new(
-                rng.gen_range(1..TILEMAP_WIDTH),
-                rng.gen_range(1..TILEMAP_HEIGHT),
+                rng.gen_range(1..mb.map_spec.width),
+                rng.gen_range(1..mb.map_spec.height),
             ))
         }
 
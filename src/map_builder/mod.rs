@@ -1,47 +1,68 @@
+mod bsp;
 mod cellular;
 mod drunkard;
 mod empty;
 mod prefab;
 mod rooms;
 mod themes;
+mod wfc;
+
+use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 use crate::utils::Rect;
 
+pub use prefab::{Prefab, PrefabSpawn, PrefabTile, Prefabs};
+pub use themes::Theme;
+
 pub trait MapTheme: Sync + Send {
     fn tile_to_render(&self, tile_type: TileType) -> usize;
 }
 
 trait MapArchitect {
-    fn new(&mut self, rng: &mut ThreadRng) -> MapBuilder;
+    /// Width and height of the maps built by this architect.
+    fn dimensions(&self) -> Point {
+        Point::new(80, 50)
+    }
+
+    fn new(&mut self, rng: &mut GameRng) -> MapBuilder {
+        let dimensions = self.dimensions();
+        self.with_dimensions(dimensions, rng)
+    }
+
+    /// Builds a map of the given width and height rather than the architect's own.
+    fn with_dimensions(&mut self, dimensions: Point, rng: &mut GameRng) -> MapBuilder;
 }
 
 pub struct MapBuilder {
     pub map_spec: MapSpec,
     pub rooms: Vec<Rect>,
     pub monster_spawns: Vec<Point>,
+    pub prefab_spawns: Vec<(Point, PrefabSpawn)>,
     pub player_start: Point,
     pub amulet_start: Point,
-    pub theme: Box<dyn MapTheme>,
 }
 
 impl MapBuilder {
     const NUM_ROOMS: usize = 20;
+    const ROOM_ATTEMPTS: usize = 1000;
     const NUM_MONSTERS: usize = 50;
 
-    fn new(rng: &mut ThreadRng) -> Self {
-        let mut architect: Box<dyn MapArchitect> = match rng.gen_range(0..3) {
+    fn new(level: usize, prefabs: &Prefabs, rng: &mut GameRng) -> Self {
+        let mut architect: Box<dyn MapArchitect> = match rng.gen_range(0..5) {
             0 => Box::new(drunkard::DrunkardsWalkArchitect),
             1 => Box::new(rooms::RoomsArchitect),
+            2 => Box::new(bsp::BspArchitect),
+            3 => Box::new(wfc::WfcArchitect),
             _ => Box::new(cellular::CellularAutomataArchitect),
         };
         let mut mb = architect.new(rng);
 
-        prefab::apply_prefab(&mut mb, rng);
+        prefab::apply_prefabs(&mut mb, level, prefabs, rng);
 
-        mb.theme = match rng.gen_range(0..2) {
-            0 => themes::DungeonTheme::new(),
-            _ => themes::ForestTheme::new(),
+        mb.map_spec.theme = match rng.gen_range(0..2) {
+            0 => Theme::Dungeon,
+            _ => Theme::Forest,
         };
 
         mb
@@ -53,8 +74,8 @@ impl MapBuilder {
 
     fn find_most_distant(&self) -> Point {
         let dijkstra_map = DijkstraMap::new(
-            TILEMAP_WIDTH,
-            TILEMAP_HEIGHT,
+            self.map_spec.width,
+            self.map_spec.height,
             &vec![self.map_spec.point2d_to_index(self.player_start)],
             &self.map_spec,
             1024.0,
@@ -74,12 +95,18 @@ impl MapBuilder {
     }
 
     fn build_random_rooms(&mut self, rng: &mut impl Rng) {
-        while self.rooms.len() < Self::NUM_ROOMS {
+        // Rooms shrink to fit small maps, where fewer of them fit too
+        let max_size = i32::min(10, i32::min(self.map_spec.width, self.map_spec.height) - 2);
+        for _ in 0..Self::ROOM_ATTEMPTS {
+            if self.rooms.len() >= Self::NUM_ROOMS {
+                break;
+            }
+
             let room = Rect::with_size(
-                rng.gen_range(1..TILEMAP_WIDTH - 10),
-                rng.gen_range(1..TILEMAP_HEIGHT - 10),
-                rng.gen_range(2..10),
-                rng.gen_range(2..10),
+                rng.gen_range(1..self.map_spec.width - max_size),
+                rng.gen_range(1..self.map_spec.height - max_size),
+                rng.gen_range(2..max_size),
+                rng.gen_range(2..max_size),
             );
 
             let mut overlap = false;
@@ -90,10 +117,11 @@ impl MapBuilder {
             }
 
             if !overlap {
+                let map_spec = &mut self.map_spec;
                 room.for_each(|p| {
-                    if p.x > 0 && p.x < TILEMAP_WIDTH && p.y > 0 && p.y < TILEMAP_HEIGHT {
-                        let idx = map_idx(p.x, p.y);
-                        self.map_spec.tiles[idx] = TileType::Floor;
+                    if p.x > 0 && p.x < map_spec.width && p.y > 0 && p.y < map_spec.height {
+                        let idx = map_spec.map_idx(p.x, p.y);
+                        map_spec.tiles[idx] = TileType::Floor;
                     }
                 });
 
@@ -154,7 +182,7 @@ impl MapBuilder {
             .collect();
 
         let mut spawns = Vec::new();
-        for _ in 0..MapBuilder::NUM_MONSTERS {
+        for _ in 0..usize::min(MapBuilder::NUM_MONSTERS, spawnable_tiles.len()) {
             let target_index = rng.gen_range(0..spawnable_tiles.len());
             spawns.push(spawnable_tiles.remove(target_index));
         }
@@ -163,44 +191,163 @@ impl MapBuilder {
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
     Exit,
+    UpStairs,
 }
 
+impl TileType {
+    /// Whether stepping on the tile changes level.
+    pub fn is_stairs(self) -> bool {
+        matches!(self, TileType::Exit | TileType::UpStairs)
+    }
+}
+
+/// Directions actors can step in, chosen for a whole game.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Movement {
+    #[default]
+    Cardinal,
+    EightWay,
+}
+
+const CARDINAL_DIRECTIONS: [Point; 4] = [
+    Point { x: -1, y: 0 },
+    Point { x: 1, y: 0 },
+    Point { x: 0, y: -1 },
+    Point { x: 0, y: 1 },
+];
+
+const EIGHT_WAY_DIRECTIONS: [Point; 8] = [
+    Point { x: -1, y: 0 },
+    Point { x: 1, y: 0 },
+    Point { x: 0, y: -1 },
+    Point { x: 0, y: 1 },
+    Point { x: -1, y: -1 },
+    Point { x: 1, y: -1 },
+    Point { x: -1, y: 1 },
+    Point { x: 1, y: 1 },
+];
+
+impl Movement {
+    /// Single steps, cardinal ones first.
+    pub fn directions(&self) -> &'static [Point] {
+        match self {
+            Movement::Cardinal => &CARDINAL_DIRECTIONS,
+            Movement::EightWay => &EIGHT_WAY_DIRECTIONS,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct MapSpec {
+    pub width: i32,
+    pub height: i32,
     pub tiles: Vec<TileType>,
     pub revealed_tiles: Vec<bool>,
-}
-
-pub fn map_idx(x: i32, y: i32) -> usize {
-    ((y * TILEMAP_WIDTH) + x) as usize
+    pub theme: Theme,
+    pub movement: Movement,
 }
 
 impl MapSpec {
-    const NUM_TILES: usize = (TILEMAP_WIDTH * TILEMAP_HEIGHT) as usize;
-
-    fn new() -> Self {
+    pub fn new(dimensions: Point) -> Self {
+        let num_tiles = (dimensions.x * dimensions.y) as usize;
         Self {
-            tiles: vec![TileType::Floor; Self::NUM_TILES],
-            revealed_tiles: vec![false; Self::NUM_TILES],
+            width: dimensions.x,
+            height: dimensions.y,
+            tiles: vec![TileType::Floor; num_tiles],
+            revealed_tiles: vec![false; num_tiles],
+            theme: Theme::Dungeon,
+            movement: Movement::default(),
         }
     }
 
+    pub fn num_tiles(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn map_idx(&self, x: i32, y: i32) -> usize {
+        ((y * self.width) + x) as usize
+    }
+
+    /// Offset of map points from tilemap points, the tilemap being centered on the origin.
+    pub fn tilemap_offset(&self) -> Point {
+        Point::new(self.width / 2, self.height / 2)
+    }
+
+    pub fn tilemap_pos(&self, point: Point) -> (i32, i32) {
+        let offset = self.tilemap_offset();
+        (point.x - offset.x, point.y - offset.y)
+    }
+
     pub fn try_idx(&self, point: Point) -> Option<usize> {
         if !self.in_bounds(point) {
             None
         } else {
-            Some(map_idx(point.x, point.y))
+            Some(self.map_idx(point.x, point.y))
         }
     }
 
     pub fn can_enter_tile(&self, point: Point) -> bool {
         self.in_bounds(point)
-            && (self.tiles[map_idx(point.x, point.y)] == TileType::Floor
-                || self.tiles[map_idx(point.x, point.y)] == TileType::Exit)
+            && matches!(
+                self.tiles[self.map_idx(point.x, point.y)],
+                TileType::Floor | TileType::Exit | TileType::UpStairs
+            )
+    }
+
+    pub fn find_tile(&self, tile: TileType) -> Option<Point> {
+        self.tiles
+            .iter()
+            .position(|&t| t == tile)
+            .map(|idx| self.index_to_point2d(idx))
+    }
+
+    /// A free floor tile next to the given point, to arrive beside stairs rather than on them
+    /// or on an occupied tile (monster, trap or item). Falls back to the closest free floor
+    /// tile when the neighbours are taken.
+    pub fn next_to(&self, point: Point, occupied: &[Point]) -> Point {
+        let is_free = |pt: Point| {
+            self.in_bounds(pt)
+                && self.tiles[self.map_idx(pt.x, pt.y)] == TileType::Floor
+                && !occupied.contains(&pt)
+        };
+
+        if let Some(pt) = self
+            .movement
+            .directions()
+            .iter()
+            .map(|&delta| point + delta)
+            .find(|&pt| is_free(pt))
+        {
+            return pt;
+        }
+
+        (0..self.num_tiles())
+            .map(|idx| self.index_to_point2d(idx))
+            .filter(|&pt| is_free(pt))
+            .min_by(|&a, &b| {
+                let distance_a = DistanceAlg::Pythagoras.distance2d(point, a);
+                let distance_b = DistanceAlg::Pythagoras.distance2d(point, b);
+                distance_a.partial_cmp(&distance_b).unwrap()
+            })
+            .unwrap_or(point)
+    }
+
+    /// Whether a single step leads from one point to the other.
+    pub fn is_adjacent(&self, from: Point, to: Point) -> bool {
+        self.movement.directions().contains(&(to - from))
+    }
+
+    /// Whether nothing opaque stands between two points, both ends excepted.
+    pub fn has_line_of_fire(&self, from: Point, to: Point) -> bool {
+        line2d_bresenham(from, to)
+            .iter()
+            .filter(|&&pt| pt != from && pt != to)
+            .all(|&pt| self.in_bounds(pt) && !self.is_opaque(self.point2d_to_index(pt)))
     }
 
     fn valid_exit(&self, loc: Point, delta: Point) -> Option<usize> {
@@ -224,17 +371,11 @@ impl BaseMap for MapSpec {
         let mut exits = SmallVec::new();
         let location = self.index_to_point2d(idx);
 
-        if let Some(idx) = self.valid_exit(location, Point::new(-1, 0)) {
-            exits.push((idx, 1.0))
-        }
-        if let Some(idx) = self.valid_exit(location, Point::new(1, 0)) {
-            exits.push((idx, 1.0))
-        }
-        if let Some(idx) = self.valid_exit(location, Point::new(0, -1)) {
-            exits.push((idx, 1.0))
-        }
-        if let Some(idx) = self.valid_exit(location, Point::new(0, 1)) {
-            exits.push((idx, 1.0))
+        for &delta in self.movement.directions() {
+            if let Some(idx) = self.valid_exit(location, delta) {
+                let diagonal = delta.x != 0 && delta.y != 0;
+                exits.push((idx, if diagonal { 1.45 } else { 1.0 }))
+            }
         }
 
         exits
@@ -247,11 +388,11 @@ impl BaseMap for MapSpec {
 
 impl Algorithm2D for MapSpec {
     fn dimensions(&self) -> Point {
-        Point::new(TILEMAP_WIDTH, TILEMAP_HEIGHT)
+        Point::new(self.width, self.height)
     }
 
     fn point2d_to_index(&self, pt: Point) -> usize {
-        map_idx(pt.x, pt.y)
+        self.map_idx(pt.x, pt.y)
     }
 
     fn index_to_point2d(&self, idx: usize) -> Point {
@@ -265,18 +406,21 @@ impl Algorithm2D for MapSpec {
     }
 }
 
-pub fn move_sprite(tilemap: &mut Tilemap, prev_pos: Point, new_pos: Point, render: &Render) {
+pub fn move_sprite(
+    tilemap: &mut Tilemap,
+    map_spec: &MapSpec,
+    prev_pos: Point,
+    new_pos: Point,
+    render: &Render,
+) {
     // We need to first remove where we were prior.
     tilemap
-        .clear_tile(
-            (prev_pos.x - CAMERA_OFFSET_X, prev_pos.y - CAMERA_OFFSET_Y),
-            render.sprite_order,
-        )
+        .clear_tile(map_spec.tilemap_pos(prev_pos), render.sprite_order)
         .unwrap();
 
     // We then need to update where we are going!
     let tile = Tile {
-        point: (new_pos.x - CAMERA_OFFSET_X, new_pos.y - CAMERA_OFFSET_Y),
+        point: map_spec.tilemap_pos(new_pos),
         sprite_index: render.sprite_index,
         sprite_order: render.sprite_order,
         ..Default::default()
@@ -285,17 +429,36 @@ pub fn move_sprite(tilemap: &mut Tilemap, prev_pos: Point, new_pos: Point, rende
     tilemap.insert_tile(tile).unwrap();
 }
 
-pub fn make_tilemap(texture_atlas: Handle<TextureAtlas>) -> (Tilemap, MapBuilder) {
-    let mut rng = rand::thread_rng();
-    let mut map_builder = MapBuilder::new(&mut rng);
+pub fn make_tilemap(
+    texture_atlas: Handle<TextureAtlas>,
+    level: usize,
+    movement: Movement,
+    prefabs: &Prefabs,
+    rng: &mut GameRng,
+) -> (Tilemap, MapBuilder) {
+    let mut map_builder = MapBuilder::new(level, prefabs, rng);
+    map_builder.map_spec.movement = movement;
 
     let exit_idx = map_builder
         .map_spec
         .point2d_to_index(map_builder.amulet_start);
     map_builder.map_spec.tiles[exit_idx] = TileType::Exit;
 
+    if level > 0 {
+        let up_idx = map_builder
+            .map_spec
+            .point2d_to_index(map_builder.player_start);
+        map_builder.map_spec.tiles[up_idx] = TileType::UpStairs;
+    }
+
+    let tilemap = build_tilemap(texture_atlas, &map_builder.map_spec);
+
+    (tilemap, map_builder)
+}
+
+pub fn build_tilemap(texture_atlas: Handle<TextureAtlas>, map_spec: &MapSpec) -> Tilemap {
     let mut tilemap = Tilemap::builder()
-        .dimensions(TILEMAP_WIDTH as u32, TILEMAP_HEIGHT as u32)
+        .dimensions(map_spec.width as u32, map_spec.height as u32)
         .chunk_dimensions(8, 8, 1)
         .texture_dimensions(32, 32)
         .texture_atlas(texture_atlas)
@@ -309,7 +472,7 @@ pub fn make_tilemap(texture_atlas: Handle<TextureAtlas>) -> (Tilemap, MapBuilder
             1,
         )
         .add_layer(
-            // items
+            // items and traps
             TilemapLayer {
                 kind: LayerKind::Sparse,
                 ..Default::default()
@@ -327,18 +490,14 @@ pub fn make_tilemap(texture_atlas: Handle<TextureAtlas>) -> (Tilemap, MapBuilder
         .finish()
         .unwrap();
 
-    let tiles = map_builder
-        .map_spec
+    let tiles = map_spec
         .tiles
         .iter()
         .enumerate()
         .map(|(i, &tile)| {
-            let sprite_index = map_builder.theme.tile_to_render(tile);
+            let sprite_index = map_spec.theme.tile_to_render(tile);
             Tile {
-                point: (
-                    (i % TILEMAP_WIDTH as usize) as i32 - CAMERA_OFFSET_X,
-                    (i / TILEMAP_WIDTH as usize) as i32 - CAMERA_OFFSET_Y,
-                ),
+                point: map_spec.tilemap_pos(map_spec.index_to_point2d(i)),
                 sprite_index,
                 sprite_order: 0,
                 tint: Color::WHITE,
@@ -347,5 +506,91 @@ pub fn make_tilemap(texture_atlas: Handle<TextureAtlas>) -> (Tilemap, MapBuilder
         .collect::<Vec<_>>();
     tilemap.insert_tiles(tiles).unwrap();
 
-    (tilemap, map_builder)
+    tilemap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the first levels one after the other, as taking the stairs down does.
+    fn build_levels(seed: u64) -> Vec<MapBuilder> {
+        let prefabs = Prefabs(prefab::load_prefabs(&Templates::load()));
+        let mut rng = GameRng::new(seed);
+        (0..3)
+            .map(|level| MapBuilder::new(level, &prefabs, &mut rng))
+            .collect()
+    }
+
+    #[test]
+    fn architects_build_small_maps() {
+        let mut architects: Vec<Box<dyn MapArchitect>> = vec![
+            Box::new(drunkard::DrunkardsWalkArchitect),
+            Box::new(rooms::RoomsArchitect),
+            Box::new(bsp::BspArchitect),
+            Box::new(wfc::WfcArchitect),
+            Box::new(cellular::CellularAutomataArchitect),
+            Box::new(empty::EmptyArchitect),
+        ];
+
+        let mut rng = GameRng::new(1234);
+        for &dimensions in [Point::new(16, 12), Point::new(8, 8)].iter() {
+            for architect in architects.iter_mut() {
+                for _ in 0..10 {
+                    let mb = architect.with_dimensions(dimensions, &mut rng);
+                    assert_eq!(mb.map_spec.dimensions(), dimensions);
+                    assert!(mb.map_spec.can_enter_tile(mb.player_start));
+                    assert!(mb.map_spec.can_enter_tile(mb.amulet_start));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn arrives_on_a_free_floor_tile() {
+        let mut map_spec = MapSpec::new(Point::new(5, 5));
+        for x in 0..5 {
+            let idx = map_spec.map_idx(x, 1);
+            map_spec.tiles[idx] = TileType::Wall;
+        }
+        let stairs = Point::new(2, 2);
+        let idx = map_spec.map_idx(stairs.x, stairs.y);
+        map_spec.tiles[idx] = TileType::UpStairs;
+
+        let occupied = [Point::new(1, 2), Point::new(2, 3)];
+        let pos = map_spec.next_to(stairs, &occupied);
+        assert!(map_spec.is_adjacent(stairs, pos));
+        assert!(map_spec.tiles[map_spec.map_idx(pos.x, pos.y)] == TileType::Floor);
+        assert!(!occupied.contains(&pos));
+
+        // Every neighbour taken, the closest free floor tile is used
+        let occupied = [
+            Point::new(1, 2),
+            Point::new(3, 2),
+            Point::new(1, 3),
+            Point::new(2, 3),
+            Point::new(3, 3),
+        ];
+        let pos = map_spec.next_to(stairs, &occupied);
+        assert_eq!(DistanceAlg::Pythagoras.distance2d(stairs, pos), 2.0);
+        assert!(map_spec.tiles[map_spec.map_idx(pos.x, pos.y)] == TileType::Floor);
+    }
+
+    #[test]
+    fn same_seed_builds_the_same_levels() {
+        let first = build_levels(1234);
+        let second = build_levels(1234);
+
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert!(a.map_spec.tiles == b.map_spec.tiles);
+            assert_eq!(a.map_spec.theme, b.map_spec.theme);
+            assert_eq!(a.player_start, b.player_start);
+            assert_eq!(a.amulet_start, b.amulet_start);
+            assert_eq!(a.monster_spawns, b.monster_spawns);
+            assert_eq!(
+                format!("{:?}", a.prefab_spawns),
+                format!("{:?}", b.prefab_spawns)
+            );
+        }
+    }
 }
@@ -1,31 +1,214 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::Path;
+
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+
 use crate::prelude::*;
 use crate::utils::Rect;
 
-const FORTRESS: (&str, i32, i32) = (
-    "
-------------
----######---
----#----#---
----#-M--#---
--###----###-
---M------M--
--###----###-
----#----#---
----#----#---
----######---
-------------
-",
-    12,
-    11,
-);
-
-pub fn apply_prefab(mb: &mut MapBuilder, rng: &mut impl Rng) {
+const PREFABS_DIR: &str = "assets/prefabs";
+const MAX_PREFABS: usize = 3;
+
+/// What a character of a prefab layout stands for.
+#[derive(Clone, Deserialize, Debug)]
+pub enum PrefabTile {
+    Wall,
+    Floor,
+    Exit,
+    Monster,
+    Item,
+    Template(String),
+}
+
+/// Entity a prefab asks for on one of its floor tiles.
+#[derive(Clone, Debug)]
+pub enum PrefabSpawn {
+    Monster,
+    Item,
+    Template(String),
+}
+
+/// Prefabs of the assets folder, loaded once.
+pub struct Prefabs(pub Vec<Prefab>);
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Prefab {
+    pub name: String,
+    pub levels: HashSet<usize>,
+    pub frequency: i32,
+    #[serde(default)]
+    pub can_rotate: bool,
+    #[serde(default)]
+    pub can_mirror: bool,
+    pub legend: HashMap<char, PrefabTile>,
+    pub layout: String,
+}
+
+impl Prefab {
+    fn load(path: &Path, templates: &Templates) -> Result<Self, Box<dyn Error>> {
+        let prefab: Prefab = ron::de::from_reader(File::open(path)?)?;
+        prefab.validate(templates)?;
+        Ok(prefab)
+    }
+
+    fn rows(&self) -> Vec<Vec<char>> {
+        self.layout
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.chars().collect())
+            .collect()
+    }
+
+    fn validate(&self, templates: &Templates) -> Result<(), Box<dyn Error>> {
+        for (c, tile) in self.legend.iter() {
+            if let PrefabTile::Template(name) = tile {
+                if !templates.entities.iter().any(|t| &t.name == name) {
+                    return Err(format!(
+                        "prefab {} legend [{}] names {}, which isn't a template",
+                        self.name, c, name
+                    )
+                    .into());
+                }
+            }
+        }
+
+        let rows = self.rows();
+        if rows.is_empty() {
+            return Err(format!("prefab {} has an empty layout", self.name).into());
+        }
+
+        for (y, row) in rows.iter().enumerate() {
+            if row.len() != rows[0].len() {
+                return Err(format!(
+                    "prefab {} row {} is {} wide instead of {}",
+                    self.name,
+                    y + 1,
+                    row.len(),
+                    rows[0].len()
+                )
+                .into());
+            }
+            if let Some(c) = row.iter().find(|c| !self.legend.contains_key(c)) {
+                return Err(format!(
+                    "prefab {} row {} uses [{}] which is missing from the legend",
+                    self.name,
+                    y + 1,
+                    c
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The layout, randomly rotated and mirrored when the prefab allows it.
+    fn transformed_rows(&self, rng: &mut impl Rng) -> Vec<Vec<char>> {
+        let mut rows = self.rows();
+
+        if self.can_rotate {
+            for _ in 0..rng.gen_range(0..4) {
+                rows = rotate_clockwise(&rows);
+            }
+        }
+
+        if self.can_mirror && rng.gen_range(0..2) == 1 {
+            rows.iter_mut().for_each(|row| row.reverse());
+        }
+
+        rows
+    }
+}
+
+fn rotate_clockwise(rows: &[Vec<char>]) -> Vec<Vec<char>> {
+    (0..rows[0].len())
+        .map(|x| rows.iter().rev().map(|row| row[x]).collect())
+        .collect()
+}
+
+/// Loads every prefab of the assets folder, skipping the ones that fail to parse or name
+/// unknown templates.
+pub fn load_prefabs(templates: &Templates) -> Vec<Prefab> {
+    let entries = match fs::read_dir(PREFABS_DIR) {
+        Ok(entries) => entries,
+        Err(err) => {
+            println!("Warning: unable to read {}: {}", PREFABS_DIR, err);
+            return Vec::new();
+        }
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .collect();
+    // Keep the library order stable so seeded levels are reproducible
+    paths.sort();
+
+    paths
+        .iter()
+        .filter_map(|path| match Prefab::load(path, templates) {
+            Ok(prefab) => Some(prefab),
+            Err(err) => {
+                println!("Warning: failed loading {}: {}", path.display(), err);
+                None
+            }
+        })
+        .collect()
+}
+
+impl FromWorld for Prefabs {
+    fn from_world(world: &mut World) -> Self {
+        let templates = world.get_resource_or_insert_with(Templates::load);
+        Prefabs(load_prefabs(&templates))
+    }
+}
+
+pub fn apply_prefabs(mb: &mut MapBuilder, level: usize, prefabs: &Prefabs, rng: &mut impl Rng) {
+    let mut available_prefabs = Vec::new();
+    prefabs
+        .0
+        .iter()
+        .filter(|p| p.levels.contains(&level))
+        .for_each(|p| {
+            for _ in 0..p.frequency {
+                available_prefabs.push(p);
+            }
+        });
+
+    let mut placed = Vec::new();
+    for _ in 0..MAX_PREFABS {
+        if let Some(&prefab) = available_prefabs.choose(rng) {
+            if let Some(area) = apply_prefab(mb, prefab, &placed, rng) {
+                placed.push(area);
+            }
+        }
+    }
+}
+
+fn apply_prefab(
+    mb: &mut MapBuilder,
+    prefab: &Prefab,
+    placed: &[Rect],
+    rng: &mut impl Rng,
+) -> Option<Rect> {
+    let rows = prefab.transformed_rows(rng);
+    let width = rows[0].len() as i32;
+    let height = rows.len() as i32;
+    if width >= mb.map_spec.width || height >= mb.map_spec.height {
+        println!("Warning: prefab {} doesn't fit on the map", prefab.name);
+        return None;
+    }
+
     let mut placement = None;
 
     let dijkstra_map = DijkstraMap::new(
-        TILEMAP_WIDTH,
-        TILEMAP_HEIGHT,
-        &vec![mb.map_spec.point2d_to_index(mb.player_start)],
+        mb.map_spec.width,
+        mb.map_spec.height,
+        &[mb.map_spec.point2d_to_index(mb.player_start)],
         &mb.map_spec,
         1024.0,
     );
@@ -33,55 +216,161 @@ pub fn apply_prefab(mb: &mut MapBuilder, rng: &mut impl Rng) {
     let mut attempts = 0;
     while placement.is_none() && attempts < 10 {
         let dimensions = Rect::with_size(
-            rng.gen_range(0..TILEMAP_WIDTH - FORTRESS.1),
-            rng.gen_range(0..TILEMAP_HEIGHT - FORTRESS.2),
-            FORTRESS.1,
-            FORTRESS.2,
+            rng.gen_range(0..mb.map_spec.width - width),
+            rng.gen_range(0..mb.map_spec.height - height),
+            width,
+            height,
         );
 
         let mut can_place = false;
         dimensions.for_each(|pt| {
             let idx = mb.map_spec.point2d_to_index(pt);
             let distance = dijkstra_map.map[idx];
-            if distance < 2000.0 && distance > 20.0 && mb.amulet_start != pt {
+            if distance < 2000.0 && distance > 20.0 {
                 can_place = true;
             }
         });
 
+        let points = dimensions.point_set();
+        if points.contains(&mb.amulet_start)
+            || points.contains(&mb.player_start)
+            || placed.iter().any(|area| area.intersect(&dimensions))
+        {
+            can_place = false;
+        }
+
         if can_place {
-            placement = Some(Point::new(dimensions.x1, dimensions.y1));
-            let points = dimensions.point_set();
+            placement = Some(dimensions);
             mb.monster_spawns.retain(|pt| !points.contains(pt));
+            mb.prefab_spawns.retain(|(pt, _)| !points.contains(pt));
         }
 
         attempts += 1;
     }
 
-    if let Some(placement) = placement {
-        let string_vec: Vec<char> = FORTRESS
-            .0
-            .chars()
-            .filter(|&a| a != '\r' && a != '\n')
-            .collect();
-
-        let mut i = 0;
-        for ty in placement.y..placement.y + FORTRESS.2 {
-            for tx in placement.x..placement.x + FORTRESS.1 {
-                let idx = map_idx(tx, ty);
-                let c = string_vec[i];
+    if let Some(area) = placement {
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                let pt = Point::new(area.x1 + x as i32, area.y1 + y as i32);
+                let idx = mb.map_spec.point2d_to_index(pt);
 
-                match c {
-                    'M' => {
+                match &prefab.legend[c] {
+                    PrefabTile::Wall => mb.map_spec.tiles[idx] = TileType::Wall,
+                    PrefabTile::Floor => mb.map_spec.tiles[idx] = TileType::Floor,
+                    PrefabTile::Exit => {
                         mb.map_spec.tiles[idx] = TileType::Floor;
-                        mb.monster_spawns.push(Point::new(tx, ty));
+                        mb.amulet_start = pt;
+                    }
+                    PrefabTile::Monster => {
+                        mb.map_spec.tiles[idx] = TileType::Floor;
+                        mb.prefab_spawns.push((pt, PrefabSpawn::Monster));
+                    }
+                    PrefabTile::Item => {
+                        mb.map_spec.tiles[idx] = TileType::Floor;
+                        mb.prefab_spawns.push((pt, PrefabSpawn::Item));
+                    }
+                    PrefabTile::Template(name) => {
+                        mb.map_spec.tiles[idx] = TileType::Floor;
+                        mb.prefab_spawns
+                            .push((pt, PrefabSpawn::Template(name.clone())));
                     }
-                    '-' => mb.map_spec.tiles[idx] = TileType::Floor,
-                    '#' => mb.map_spec.tiles[idx] = TileType::Wall,
-                    _ => println!("No idea what to do with [{}]", c),
                 }
-
-                i += 1;
             }
         }
     }
+
+    placement
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefab(legend: &str, layout: &str) -> Prefab {
+        ron::de::from_str(&format!(
+            "Prefab(name: \"Test\", levels: [0], frequency: 1, can_rotate: true, \
+             can_mirror: true, legend: {{ {} }}, layout: \"{}\")",
+            legend, layout
+        ))
+        .unwrap()
+    }
+
+    fn layout(rows: &[&str]) -> Vec<Vec<char>> {
+        rows.iter().map(|row| row.chars().collect()).collect()
+    }
+
+    #[test]
+    fn shipped_prefabs_are_valid() {
+        let templates = Templates::load();
+        let count = fs::read_dir(PREFABS_DIR).unwrap().count();
+        assert_eq!(load_prefabs(&templates).len(), count);
+    }
+
+    #[test]
+    fn validate_accepts_a_complete_legend() {
+        let prefab = prefab("'#': Wall, '-': Floor, 'o': Template(\"Orc\")", "#-\n-o");
+        assert!(prefab.validate(&Templates::load()).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_ragged_rows() {
+        let prefab = prefab("'#': Wall, '-': Floor", "##\n#-#");
+        assert!(prefab.validate(&Templates::load()).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_characters_missing_from_the_legend() {
+        let prefab = prefab("'#': Wall", "##\n#-");
+        assert!(prefab.validate(&Templates::load()).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_templates() {
+        let prefab = prefab("'#': Wall, 'x': Template(\"Nobody\")", "#x");
+        assert!(prefab.validate(&Templates::load()).is_err());
+    }
+
+    #[test]
+    fn rotates_clockwise() {
+        let rows = layout(&["ab", "cd", "ef"]);
+        let rotated = rotate_clockwise(&rows);
+        assert_eq!(rotated, layout(&["eca", "fdb"]));
+
+        let full_turn = (0..3).fold(rotated, |rows, _| rotate_clockwise(&rows));
+        assert_eq!(full_turn, rows);
+    }
+
+    #[test]
+    fn transforms_stay_within_the_allowed_ones() {
+        let mut prefab = prefab("'#': Wall, '-': Floor", "##-\n#--");
+        let rows = prefab.rows();
+        let quarter = rotate_clockwise(&rows);
+        let half = rotate_clockwise(&quarter);
+        let three_quarters = rotate_clockwise(&half);
+        let rotations = [rows.clone(), quarter, half, three_quarters];
+        let mirrored = |rows: &Vec<Vec<char>>| -> Vec<Vec<char>> {
+            rows.iter()
+                .map(|row| row.iter().rev().copied().collect())
+                .collect()
+        };
+
+        let mut rng = GameRng::new(1234);
+        for _ in 0..20 {
+            let transformed = prefab.transformed_rows(&mut rng);
+            assert!(rotations
+                .iter()
+                .any(|r| *r == transformed || mirrored(r) == transformed));
+        }
+
+        prefab.can_rotate = false;
+        for _ in 0..20 {
+            let transformed = prefab.transformed_rows(&mut rng);
+            assert!(transformed == rows || transformed == mirrored(&rows));
+        }
+
+        prefab.can_mirror = false;
+        for _ in 0..20 {
+            assert_eq!(prefab.transformed_rows(&mut rng), rows);
+        }
+    }
 }
@@ -3,14 +3,14 @@ use crate::prelude::*;
 pub struct RoomsArchitect;
 
 impl super::MapArchitect for RoomsArchitect {
-    fn new(&mut self, rng: &mut ThreadRng) -> MapBuilder {
+    fn with_dimensions(&mut self, dimensions: Point, rng: &mut GameRng) -> MapBuilder {
         let mut mb = MapBuilder {
-            map_spec: MapSpec::new(),
+            map_spec: MapSpec::new(dimensions),
             rooms: Vec::new(),
             monster_spawns: Vec::new(),
+            prefab_spawns: Vec::new(),
             player_start: Point::zero(),
             amulet_start: Point::zero(),
-            theme: super::themes::DungeonTheme::new(),
         };
 
         mb.fill(TileType::Wall);
@@ -1,19 +1,31 @@
+use serde::{Deserialize, Serialize};
+
 use crate::prelude::*;
 
-pub struct DungeonTheme;
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    Dungeon,
+    Forest,
+}
 
-impl DungeonTheme {
-    pub fn new() -> Box<dyn MapTheme> {
-        Box::new(Self)
+impl MapTheme for Theme {
+    fn tile_to_render(&self, tile_type: TileType) -> usize {
+        match self {
+            Theme::Dungeon => DungeonTheme.tile_to_render(tile_type),
+            Theme::Forest => ForestTheme.tile_to_render(tile_type),
+        }
     }
 }
 
+pub struct DungeonTheme;
+
 impl MapTheme for DungeonTheme {
     fn tile_to_render(&self, tile_type: TileType) -> usize {
         match tile_type {
             TileType::Floor => to_cp437('.'),
             TileType::Wall => to_cp437('#'),
             TileType::Exit => to_cp437('>'),
+            TileType::UpStairs => to_cp437('<'),
         }
     }
 }
@@ -26,12 +38,7 @@ impl MapTheme for ForestTheme {
             TileType::Floor => to_cp437(';'),
             TileType::Wall => to_cp437('"'),
             TileType::Exit => to_cp437('>'),
+            TileType::UpStairs => to_cp437('<'),
         }
     }
 }
-
-impl ForestTheme {
-    pub fn new() -> Box<dyn MapTheme> {
-        Box::new(Self)
-    }
-}
@@ -0,0 +1,371 @@
+use super::MapArchitect;
+use crate::prelude::*;
+
+const CHUNK_SIZE: i32 = 3;
+
+/// Collapses tried on the chunks of a sample before learning from a new sample.
+const COLLAPSE_ATTEMPTS: usize = 10;
+/// Samples tried before falling back to the rooms architect.
+const SAMPLE_ATTEMPTS: usize = 3;
+
+/// Directions to a chunk's neighbours, indexing the compatibility tables.
+const NEIGHBOURS: [Point; 4] = [
+    Point { x: 0, y: -1 },
+    Point { x: 0, y: 1 },
+    Point { x: -1, y: 0 },
+    Point { x: 1, y: 0 },
+];
+
+const SAMPLE: (&str, i32, i32) = (
+    "
+########################
+#----#-------#---------#
+#----#-------#---------#
+#----------------#-----#
+#----#-------#---#-----#
+##-####-----##---###-###
+#----#-------#---------#
+#----#---#---#---------#
+#--------#-------#######
+#----#---#---#---#-----#
+######-#######---#-----#
+#--------#-------------#
+#--------#---#---#-----#
+#--------#---#---#-----#
+########################
+",
+    24,
+    15,
+);
+
+struct Chunk {
+    tiles: Vec<TileType>,
+    /// How many times the chunk appears in the sample.
+    weight: usize,
+}
+
+pub struct WfcArchitect;
+
+impl MapArchitect for WfcArchitect {
+    fn with_dimensions(&mut self, dimensions: Point, rng: &mut GameRng) -> MapBuilder {
+        let mut mb = MapBuilder {
+            map_spec: MapSpec::new(dimensions),
+            rooms: Vec::new(),
+            monster_spawns: Vec::new(),
+            prefab_spawns: Vec::new(),
+            player_start: Point::zero(),
+            amulet_start: Point::zero(),
+        };
+
+        let start = match self.build(&mut mb.map_spec, rng) {
+            Some(start) => start,
+            None => {
+                println!("Warning: wave function collapse keeps failing, building rooms instead");
+                return super::rooms::RoomsArchitect.with_dimensions(dimensions, rng);
+            }
+        };
+
+        mb.monster_spawns = mb.spawn_monsters(&start, rng);
+        mb.player_start = start;
+        mb.amulet_start = mb.find_most_distant();
+
+        mb
+    }
+}
+
+impl WfcArchitect {
+    /// Collapses maps until one has enough floor connected to its start, learning from a new
+    /// sample when the chunks of the current one keep failing. Gives up after
+    /// `SAMPLE_ATTEMPTS` samples.
+    fn build(&self, map_spec: &mut MapSpec, rng: &mut GameRng) -> Option<Point> {
+        let desired_floor = map_spec.num_tiles() / 8;
+
+        for _ in 0..SAMPLE_ATTEMPTS {
+            let sample = self.sample(rng);
+            let chunks = self.learn_chunks(&sample);
+            let compatible = self.compatibility(&chunks);
+
+            for _ in 0..COLLAPSE_ATTEMPTS {
+                if !self.collapse(&chunks, &compatible, rng, map_spec) {
+                    continue;
+                }
+                let start = match self.find_start(map_spec) {
+                    Some(start) => start,
+                    None => continue,
+                };
+
+                let dijkstra_map = DijkstraMap::new(
+                    map_spec.width,
+                    map_spec.height,
+                    &[map_spec.point2d_to_index(start)],
+                    &*map_spec,
+                    1024.0,
+                );
+
+                dijkstra_map
+                    .map
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &distance)| distance > 2000.0)
+                    .for_each(|(idx, _)| map_spec.tiles[idx] = TileType::Wall);
+
+                let floor = map_spec
+                    .tiles
+                    .iter()
+                    .filter(|&&t| t == TileType::Floor)
+                    .count();
+                if floor >= desired_floor {
+                    return Some(start);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Builds the map the tile patterns are learnt from, either with another architect or
+    /// from the built-in sample.
+    fn sample(&self, rng: &mut GameRng) -> MapSpec {
+        match rng.gen_range(0..4) {
+            0 => super::rooms::RoomsArchitect.new(rng).map_spec,
+            1 => super::bsp::BspArchitect.new(rng).map_spec,
+            2 => super::cellular::CellularAutomataArchitect.new(rng).map_spec,
+            _ => {
+                let mut map_spec = MapSpec::new(Point::new(SAMPLE.1, SAMPLE.2));
+                SAMPLE
+                    .0
+                    .chars()
+                    .filter(|&a| a != '\r' && a != '\n')
+                    .zip(map_spec.tiles.iter_mut())
+                    .for_each(|(c, t)| {
+                        // '-' marks the floor, '#' or anything else is a wall
+                        *t = match c {
+                            '-' => TileType::Floor,
+                            _ => TileType::Wall,
+                        }
+                    });
+                map_spec
+            }
+        }
+    }
+
+    /// Learns every chunk of the sample, chunks overlap so patterns can continue in any direction.
+    fn learn_chunks(&self, sample: &MapSpec) -> Vec<Chunk> {
+        let mut chunks: Vec<Chunk> = Vec::new();
+        for cy in 0..=sample.height - CHUNK_SIZE {
+            for cx in 0..=sample.width - CHUNK_SIZE {
+                let mut tiles = Vec::new();
+                for y in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        tiles.push(sample.tiles[sample.map_idx(cx + x, cy + y)]);
+                    }
+                }
+
+                match chunks.iter_mut().find(|chunk| chunk.tiles == tiles) {
+                    Some(chunk) => chunk.weight += 1,
+                    None => chunks.push(Chunk { tiles, weight: 1 }),
+                }
+            }
+        }
+        chunks
+    }
+
+    fn edge(&self, chunk: &[TileType], direction: Point) -> Vec<TileType> {
+        (0..CHUNK_SIZE)
+            .map(|i| {
+                let (x, y) = match (direction.x, direction.y) {
+                    (0, -1) => (i, 0),
+                    (0, _) => (i, CHUNK_SIZE - 1),
+                    (-1, _) => (0, i),
+                    _ => (CHUNK_SIZE - 1, i),
+                };
+                chunk[(y * CHUNK_SIZE + x) as usize]
+            })
+            .collect()
+    }
+
+    /// For each direction, whether a chunk can be placed next to another one.
+    fn compatibility(&self, chunks: &[Chunk]) -> Vec<Vec<Vec<bool>>> {
+        let edges: Vec<Vec<Vec<TileType>>> = chunks
+            .iter()
+            .map(|chunk| {
+                NEIGHBOURS
+                    .iter()
+                    .map(|&direction| self.edge(&chunk.tiles, direction))
+                    .collect()
+            })
+            .collect();
+
+        (0..NEIGHBOURS.len())
+            .map(|direction| {
+                // Neighbours come in pairs of opposite directions
+                let opposite = direction ^ 1;
+                edges
+                    .iter()
+                    .map(|edge| {
+                        edges
+                            .iter()
+                            .map(|other| edge[direction] == other[opposite])
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Fills the map with chunks fitting their neighbours, returns false on a contradiction.
+    fn collapse(
+        &self,
+        chunks: &[Chunk],
+        compatible: &[Vec<Vec<bool>>],
+        rng: &mut GameRng,
+        map_spec: &mut MapSpec,
+    ) -> bool {
+        let chunks_x = map_spec.width / CHUNK_SIZE;
+        let chunks_y = map_spec.height / CHUNK_SIZE;
+        let num_cells = (chunks_x * chunks_y) as usize;
+
+        let mut placed: Vec<Option<usize>> = vec![None; num_cells];
+        let mut options: Vec<Vec<usize>> = vec![(0..chunks.len()).collect(); num_cells];
+
+        for _ in 0..num_cells {
+            // Collapse one of the most constrained cells
+            let min_options = (0..num_cells)
+                .filter(|&cell| placed[cell].is_none())
+                .map(|cell| options[cell].len())
+                .min()
+                .unwrap();
+            let cells: Vec<usize> = (0..num_cells)
+                .filter(|&cell| placed[cell].is_none() && options[cell].len() == min_options)
+                .collect();
+            let cell = cells[rng.gen_range(0..cells.len())];
+
+            let total_weight: usize = options[cell].iter().map(|&c| chunks[c].weight).sum();
+            let mut roll = rng.gen_range(0..total_weight);
+            let chunk = *options[cell]
+                .iter()
+                .find(|&&c| {
+                    if roll < chunks[c].weight {
+                        true
+                    } else {
+                        roll -= chunks[c].weight;
+                        false
+                    }
+                })
+                .unwrap();
+            placed[cell] = Some(chunk);
+            options[cell] = vec![chunk];
+
+            // Propagate the choice until every cell's options fit its neighbours' options
+            let mut changed = vec![cell];
+            while let Some(cell) = changed.pop() {
+                let pos = Point::new(cell as i32 % chunks_x, cell as i32 / chunks_x);
+                for (direction, &delta) in NEIGHBOURS.iter().enumerate() {
+                    let neighbour = pos + delta;
+                    if neighbour.x < 0
+                        || neighbour.x >= chunks_x
+                        || neighbour.y < 0
+                        || neighbour.y >= chunks_y
+                    {
+                        continue;
+                    }
+
+                    let neighbour_idx = (neighbour.y * chunks_x + neighbour.x) as usize;
+                    let mut supported = vec![false; chunks.len()];
+                    for &chunk in options[cell].iter() {
+                        for (other, fits) in compatible[direction][chunk].iter().enumerate() {
+                            supported[other] |= fits;
+                        }
+                    }
+
+                    let count = options[neighbour_idx].len();
+                    options[neighbour_idx].retain(|&other| supported[other]);
+                    if options[neighbour_idx].is_empty() {
+                        return false;
+                    }
+                    if options[neighbour_idx].len() != count {
+                        changed.push(neighbour_idx);
+                    }
+                }
+            }
+        }
+
+        map_spec.tiles.iter_mut().for_each(|t| *t = TileType::Wall);
+        for (cell, chunk) in placed.iter().enumerate() {
+            let chunk = &chunks[chunk.unwrap()].tiles;
+            let origin = Point::new(
+                cell as i32 % chunks_x * CHUNK_SIZE,
+                cell as i32 / chunks_x * CHUNK_SIZE,
+            );
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let idx = map_spec.map_idx(origin.x + x, origin.y + y);
+                    map_spec.tiles[idx] = chunk[(y * CHUNK_SIZE + x) as usize];
+                }
+            }
+        }
+
+        // Keep the map closed
+        for x in 0..map_spec.width {
+            let top = map_spec.map_idx(x, 0);
+            let bottom = map_spec.map_idx(x, map_spec.height - 1);
+            map_spec.tiles[top] = TileType::Wall;
+            map_spec.tiles[bottom] = TileType::Wall;
+        }
+        for y in 0..map_spec.height {
+            let left = map_spec.map_idx(0, y);
+            let right = map_spec.map_idx(map_spec.width - 1, y);
+            map_spec.tiles[left] = TileType::Wall;
+            map_spec.tiles[right] = TileType::Wall;
+        }
+
+        true
+    }
+
+    fn find_start(&self, map_spec: &MapSpec) -> Option<Point> {
+        let center = Point::new(map_spec.width / 2, map_spec.height / 2);
+        map_spec
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, &t)| t == TileType::Floor)
+            .map(|(idx, _)| {
+                (
+                    idx,
+                    DistanceAlg::Pythagoras.distance2d(center, map_spec.index_to_point2d(idx)),
+                )
+            })
+            .min_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap())
+            .map(|(idx, _)| map_spec.index_to_point2d(idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_connected_maps_with_enough_floor() {
+        let mut rng = GameRng::new(1234);
+        for _ in 0..5 {
+            let mut map_spec = MapSpec::new(WfcArchitect.dimensions());
+            let start = WfcArchitect
+                .build(&mut map_spec, &mut rng)
+                .expect("wave function collapse gave up");
+
+            let floor: Vec<usize> = (0..map_spec.num_tiles())
+                .filter(|&idx| map_spec.tiles[idx] == TileType::Floor)
+                .collect();
+            assert!(floor.len() >= map_spec.num_tiles() / 8);
+
+            let dijkstra_map = DijkstraMap::new(
+                map_spec.width,
+                map_spec.height,
+                &[map_spec.point2d_to_index(start)],
+                &map_spec,
+                1024.0,
+            );
+            assert!(floor.iter().all(|&idx| dijkstra_map.map[idx] < 2000.0));
+        }
+    }
+}
@@ -0,0 +1,423 @@
+use std::error::Error;
+use std::fs::File;
+
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+pub const SAVE_FILE: &str = "savegame.ron";
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub turn_state: TurnState,
+    pub map_spec: MapSpec,
+    pub entities: Vec<SavedEntity>,
+    pub dungeon: Dungeon,
+}
+
+impl SaveGame {
+    pub fn capture(world: &mut World) -> Self {
+        let turn_state = world
+            .get_resource::<State<TurnState>>()
+            .unwrap()
+            .current()
+            .clone();
+        let map_spec = world.get_resource::<MapSpec>().unwrap().clone();
+        let dungeon = world.get_resource::<Dungeon>().cloned().unwrap_or_default();
+
+        let entities = world
+            .query_filtered::<Entity, Or<(With<Player>, With<Enemy>, With<Item>, With<Trap>)>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        let mut entities = entities
+            .into_iter()
+            .map(|entity| SavedEntity::capture(world, entity))
+            .collect::<Vec<_>>();
+
+        // The player goes first so that carried items can refer to it when loading
+        entities.sort_by_key(|saved| saved.player.is_none());
+
+        Self {
+            turn_state,
+            map_spec,
+            entities,
+            dungeon,
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let file = File::create(path)?;
+        ron::ser::to_writer_pretty(file, self, PrettyConfig::new())?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        Ok(ron::de::from_reader(file)?)
+    }
+
+    /// Spawns the saved entities and their sprites.
+    pub fn restore(
+        &self,
+        commands: &mut Commands,
+        tilemap: &mut Tilemap,
+        font_handle: &Handle<Font>,
+    ) {
+        let mut player = None;
+        let mut equipment = Equipment::default();
+        for saved in self.entities.iter() {
+            let entity = saved.restore(commands, tilemap, &self.map_spec, player, font_handle);
+            if saved.player.is_some() {
+                player = Some(entity);
+            }
+            if let (true, Some(slot)) = (saved.carried, saved.equipped) {
+                equipment.0.insert(slot, entity);
+            }
+        }
+        if let Some(player) = player {
+            commands.entity(player).insert(equipment);
+        }
+    }
+}
+
+/// Components of a single entity, missing fields default to an absent component.
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedEntity {
+    pub player: Option<Player>,
+    pub enemy: bool,
+    pub item: bool,
+    pub position: Option<(i32, i32)>,
+    pub carried: bool,
+    pub render: Option<Render>,
+    pub name: Option<Name>,
+    pub description: Option<Description>,
+    pub health: Option<Health>,
+    pub damage: Option<Damage>,
+    pub defense: Option<Defense>,
+    pub accuracy: Option<Accuracy>,
+    pub evasion: Option<Evasion>,
+    pub fov_radius: Option<i32>,
+    pub speed: Option<Speed>,
+    pub energy: Option<Energy>,
+    pub experience: Option<Experience>,
+    pub experience_value: Option<ExperienceValue>,
+    pub loot_table: Option<LootTable>,
+    pub status_effects: Option<StatusEffects>,
+    pub inflicts_effects: Option<InflictsEffects>,
+    pub provides_effects: Option<ProvidesEffects>,
+    pub trap: bool,
+    pub chasing_player: bool,
+    pub moving_randomly: bool,
+    pub amulet_of_yala: bool,
+    pub weapon: bool,
+    pub ranged_weapon: Option<RangedWeapon>,
+    pub armor: bool,
+    pub equippable: Option<EquipmentSlot>,
+    /// Slot of the carrier's equipment the item is in.
+    pub equipped: Option<EquipmentSlot>,
+    pub provides_healing: Option<ProvidesHealing>,
+    pub provides_dungeon_map: bool,
+}
+
+impl SavedEntity {
+    pub fn capture(world: &World, entity: Entity) -> Self {
+        Self {
+            player: world.get::<Player>(entity).cloned(),
+            enemy: world.get::<Enemy>(entity).is_some(),
+            item: world.get::<Item>(entity).is_some(),
+            position: world.get::<Point>(entity).map(|pos| (pos.x, pos.y)),
+            carried: world.get::<Carried>(entity).is_some(),
+            render: world.get::<Render>(entity).cloned(),
+            name: world.get::<Name>(entity).cloned(),
+            description: world.get::<Description>(entity).cloned(),
+            health: world.get::<Health>(entity).cloned(),
+            damage: world.get::<Damage>(entity).cloned(),
+            defense: world.get::<Defense>(entity).cloned(),
+            accuracy: world.get::<Accuracy>(entity).cloned(),
+            evasion: world.get::<Evasion>(entity).cloned(),
+            fov_radius: world.get::<FieldOfView>(entity).map(|fov| fov.radius),
+            speed: world.get::<Speed>(entity).cloned(),
+            energy: world.get::<Energy>(entity).cloned(),
+            experience: world.get::<Experience>(entity).cloned(),
+            experience_value: world.get::<ExperienceValue>(entity).cloned(),
+            loot_table: world.get::<LootTable>(entity).cloned(),
+            status_effects: world.get::<StatusEffects>(entity).cloned(),
+            inflicts_effects: world.get::<InflictsEffects>(entity).cloned(),
+            provides_effects: world.get::<ProvidesEffects>(entity).cloned(),
+            trap: world.get::<Trap>(entity).is_some(),
+            chasing_player: world.get::<ChasingPlayer>(entity).is_some(),
+            moving_randomly: world.get::<MovingRandomly>(entity).is_some(),
+            amulet_of_yala: world.get::<AmuletOfYala>(entity).is_some(),
+            weapon: world.get::<Weapon>(entity).is_some(),
+            ranged_weapon: world.get::<RangedWeapon>(entity).cloned(),
+            armor: world.get::<Armor>(entity).is_some(),
+            equippable: world
+                .get::<Equippable>(entity)
+                .map(|&Equippable(slot)| slot),
+            equipped: world
+                .get::<Carried>(entity)
+                .and_then(|&Carried(owner)| world.get::<Equipment>(owner))
+                .and_then(|equipment| equipment.slot_of(entity)),
+            provides_healing: world.get::<ProvidesHealing>(entity).cloned(),
+            provides_dungeon_map: world.get::<ProvidesDungeonMap>(entity).is_some(),
+        }
+    }
+
+    fn position(&self) -> Option<Point> {
+        self.position.map(|(x, y)| Point::new(x, y))
+    }
+
+    pub fn restore(
+        &self,
+        commands: &mut Commands,
+        tilemap: &mut Tilemap,
+        map_spec: &MapSpec,
+        player: Option<Entity>,
+        font_handle: &Handle<Font>,
+    ) -> Entity {
+        let mut entity = commands.spawn();
+
+        if let Some(player) = &self.player {
+            entity.insert(player.clone());
+        }
+        if self.enemy {
+            entity.insert(Enemy);
+        }
+        if self.item {
+            entity.insert(Item);
+        }
+        if let Some(position) = self.position() {
+            entity.insert(position);
+        }
+        if let Some(render) = self.render {
+            entity.insert(render);
+        }
+        if let Some(name) = &self.name {
+            entity.insert(name.clone());
+        }
+        if let Some(description) = &self.description {
+            entity.insert(description.clone());
+        }
+        if let Some(health) = &self.health {
+            entity.insert(health.clone());
+        }
+        if let Some(damage) = &self.damage {
+            entity.insert(damage.clone());
+        }
+        if let Some(defense) = &self.defense {
+            entity.insert(defense.clone());
+        }
+        if let Some(accuracy) = &self.accuracy {
+            entity.insert(accuracy.clone());
+        }
+        if let Some(evasion) = &self.evasion {
+            entity.insert(evasion.clone());
+        }
+        if let Some(radius) = self.fov_radius {
+            let mut fov = FieldOfView::new(radius);
+            if let Some(position) = self.position() {
+                fov.visible_tiles = field_of_view_set(position, radius, map_spec);
+                fov.is_dirty = false;
+            }
+            entity.insert(fov);
+        }
+        if let Some(speed) = self.speed {
+            entity.insert(speed);
+        }
+        if let Some(energy) = self.energy {
+            entity.insert(energy);
+        }
+        if let Some(status_effects) = &self.status_effects {
+            entity.insert(status_effects.clone());
+        }
+        if let Some(experience) = self.experience {
+            entity.insert(experience);
+        }
+        if let Some(experience_value) = self.experience_value {
+            entity.insert(experience_value);
+        }
+        if let Some(loot_table) = &self.loot_table {
+            entity.insert(loot_table.clone());
+        }
+        if let Some(inflicts_effects) = &self.inflicts_effects {
+            entity.insert(inflicts_effects.clone());
+        }
+        if let Some(provides_effects) = &self.provides_effects {
+            entity.insert(provides_effects.clone());
+        }
+        if self.trap {
+            entity.insert(Trap);
+        }
+        if self.chasing_player {
+            entity.insert(ChasingPlayer);
+        }
+        if self.moving_randomly {
+            entity.insert(MovingRandomly);
+        }
+        if self.amulet_of_yala {
+            entity.insert(AmuletOfYala);
+        }
+        if self.weapon {
+            entity.insert(Weapon);
+        }
+        if let Some(ranged_weapon) = self.ranged_weapon {
+            entity.insert(ranged_weapon);
+        }
+        if self.armor {
+            entity.insert(Armor);
+        }
+        if let Some(slot) = self.equippable {
+            entity.insert(Equippable(slot));
+        }
+        if let Some(healing) = &self.provides_healing {
+            entity.insert(healing.clone());
+        }
+        if self.provides_dungeon_map {
+            entity.insert(ProvidesDungeonMap);
+        }
+
+        if let (true, Some(player)) = (self.carried, player) {
+            entity
+                .insert(Carried(player))
+                .insert_bundle(inventory_text(font_handle.clone()));
+        }
+
+        if let (Some(position), Some(render)) = (self.position(), self.render) {
+            tilemap
+                .insert_tile(Tile {
+                    point: map_spec.tilemap_pos(position),
+                    sprite_index: render.sprite_index,
+                    sprite_order: render.sprite_order,
+                    tint: Color::WHITE,
+                })
+                .unwrap();
+        }
+
+        entity.id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+
+    use super::*;
+
+    #[test]
+    fn saved_games_load_back() {
+        let map_spec = MapSpec::new(Point::new(10, 10));
+
+        let mut world = World::default();
+        world.insert_resource(State::new(TurnState::AwaitingInput));
+        world.insert_resource(map_spec.clone());
+
+        let mut dungeon = Dungeon::default();
+        dungeon.levels.insert(
+            0,
+            StoredLevel {
+                map_spec: map_spec.clone(),
+                entities: vec![SavedEntity {
+                    enemy: true,
+                    position: Some((7, 7)),
+                    name: Some(Name("Orc".to_string())),
+                    ..Default::default()
+                }],
+            },
+        );
+        world.insert_resource(dungeon);
+
+        let player = world
+            .spawn()
+            .insert(Player {
+                map_level: 1,
+                calm_turns: 3,
+            })
+            .insert(Point::new(1, 1))
+            .insert(Health {
+                current: 12,
+                max: 20,
+            })
+            .id();
+        let sword = world
+            .spawn()
+            .insert(Item)
+            .insert(Weapon)
+            .insert(Name("Sword".to_string()))
+            .insert(Equippable(EquipmentSlot::MainHand))
+            .insert(Carried(player))
+            .id();
+        world
+            .spawn()
+            .insert(Item)
+            .insert(Name("Healing Potion".to_string()))
+            .insert(Carried(player));
+        let mut equipment = Equipment::default();
+        equipment.0.insert(EquipmentSlot::MainHand, sword);
+        world.entity_mut(player).insert(equipment);
+        world
+            .spawn()
+            .insert(Enemy)
+            .insert(Point::new(4, 2))
+            .insert(Name("Goblin".to_string()))
+            .insert(StatusEffects(vec![
+                (StatusEffect::Confusion, 2),
+                (StatusEffect::Stun, 1),
+            ]));
+
+        let path = std::env::temp_dir().join("dungeoncrawl_saved_games_load_back.ron");
+        let path = path.to_str().unwrap();
+        SaveGame::capture(&mut world).save(path).unwrap();
+        let save_game = SaveGame::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(save_game.turn_state, TurnState::AwaitingInput);
+        let level = &save_game.dungeon.levels[&0];
+        assert_eq!(level.entities.len(), 1);
+        assert_eq!(level.entities[0].position, Some((7, 7)));
+
+        let mut world = World::default();
+        let mut tilemap = build_tilemap(Handle::default(), &save_game.map_spec);
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        save_game.restore(&mut commands, &mut tilemap, &Handle::default());
+        queue.apply(&mut world);
+
+        let (player, player_state, &player_pos, health, equipment) = world
+            .query::<(Entity, &Player, &Point, &Health, &Equipment)>()
+            .iter(&world)
+            .next()
+            .unwrap();
+        assert_eq!((player_state.map_level, player_state.calm_turns), (1, 3));
+        assert_eq!(player_pos, Point::new(1, 1));
+        assert_eq!((health.current, health.max), (12, 20));
+        let equipment = equipment.clone();
+
+        let mut carried = world
+            .query::<(Entity, &Carried, &Name)>()
+            .iter(&world)
+            .map(|(entity, carried, name)| {
+                assert!(carried.0 == player);
+                (name.0.clone(), equipment.slot_of(entity))
+            })
+            .collect::<Vec<_>>();
+        carried.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            carried,
+            vec![
+                ("Healing Potion".to_string(), None),
+                ("Sword".to_string(), Some(EquipmentSlot::MainHand)),
+            ]
+        );
+
+        let (name, &enemy_pos, effects) = world
+            .query_filtered::<(&Name, &Point, &StatusEffects), With<Enemy>>()
+            .iter(&world)
+            .next()
+            .unwrap();
+        assert_eq!(name.0, "Goblin");
+        assert_eq!(enemy_pos, Point::new(4, 2));
+        assert!(effects.has(StatusEffect::Confusion) && effects.has(StatusEffect::Stun));
+    }
+}
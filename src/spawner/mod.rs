@@ -1,5 +1,7 @@
 mod template;
 
+pub use template::Templates;
+
 use crate::prelude::*;
 
 pub fn spawn_tilemap(commands: &mut Commands, tilemap: Tilemap) {
@@ -16,21 +18,21 @@ pub fn spawn_tilemap(commands: &mut Commands, tilemap: Tilemap) {
     commands.spawn().insert_bundle(tilemap_components);
 }
 
-pub fn spawn_camera(commands: &mut Commands, player_start: Point) {
-    let mut camera = OrthographicCameraBundle::new_2d();
-
-    camera.transform.translation.x = (player_start.x as f32 - CAMERA_OFFSET_X as f32) * 32.;
-    camera.transform.translation.y = (player_start.y as f32 - CAMERA_OFFSET_Y as f32) * 32.;
-
-    commands.spawn_bundle(camera);
+pub fn spawn_camera(commands: &mut Commands) {
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
 }
 
-pub fn spawn_player(commands: &mut Commands, position: Point, tilemap: &mut Tilemap) {
+pub fn spawn_player(
+    commands: &mut Commands,
+    position: Point,
+    tilemap: &mut Tilemap,
+    map_spec: &MapSpec,
+) {
     let sprite_index = to_cp437('@');
     let sprite_order = 3;
 
     commands.spawn().insert_bundle(PlayerBundle {
-        player: Player { map_level: 0 },
+        player: Player::default(),
         position,
         render: Render {
             sprite_index,
@@ -41,12 +43,17 @@ pub fn spawn_player(commands: &mut Commands, position: Point, tilemap: &mut Tile
             max: 10,
         },
         fov: FieldOfView::new(6),
-        damage: Damage(1),
+        damage: Damage(Dice::flat(1)),
+        speed: Speed::default(),
+        energy: Energy::default(),
+        experience: Experience::default(),
+        status_effects: StatusEffects::default(),
+        equipment: Equipment::default(),
     });
 
     tilemap
         .insert_tile(Tile {
-            point: (position.x - CAMERA_OFFSET_X, position.y - CAMERA_OFFSET_Y),
+            point: map_spec.tilemap_pos(position),
             sprite_index,
             sprite_order,
             tint: Color::WHITE,
@@ -55,13 +62,29 @@ pub fn spawn_player(commands: &mut Commands, position: Point, tilemap: &mut Tile
 }
 
 pub fn spawn_level(
+    templates: &Templates,
     level: usize,
     commands: &mut Commands,
     spawn_points: &[Point],
+    prefab_spawns: &[(Point, PrefabSpawn)],
     tilemap: &mut Tilemap,
+    map_spec: &MapSpec,
+    rng: &mut GameRng,
 ) {
-    let template = template::Templates::load();
-    template.spawn_entities(level, commands, spawn_points, tilemap);
+    templates.spawn_entities(level, commands, spawn_points, tilemap, map_spec, rng);
+    templates.spawn_prefab_entities(level, commands, prefab_spawns, tilemap, map_spec, rng);
+}
+
+/// Spawns the item named `name` where an enemy died, returns whether it was found.
+pub fn spawn_loot(
+    templates: &Templates,
+    commands: &mut Commands,
+    position: Point,
+    name: &str,
+    tilemap: &mut Tilemap,
+    map_spec: &MapSpec,
+) -> bool {
+    templates.spawn_loot(commands, &position, name, tilemap, map_spec)
 }
 
 pub fn spawn_amulet_of_yala(
@@ -69,19 +92,18 @@ pub fn spawn_amulet_of_yala(
     position: Point,
     tilemap: &mut Tilemap,
     map_spec: &mut MapSpec,
-    theme: &dyn MapTheme,
 ) {
     // Clean exit tile
 
     let exit_idx = map_spec.point2d_to_index(position);
     map_spec.tiles[exit_idx] = TileType::Floor;
 
-    let tilemap_position = (position.x - CAMERA_OFFSET_X, position.y - CAMERA_OFFSET_Y);
+    let tilemap_position = map_spec.tilemap_pos(position);
     tilemap.clear_tile(tilemap_position, 0).unwrap();
     tilemap
         .insert_tile(Tile {
             point: tilemap_position,
-            sprite_index: theme.tile_to_render(TileType::Floor),
+            sprite_index: map_spec.theme.tile_to_render(TileType::Floor),
             sprite_order: 0,
             tint: Color::WHITE,
         })
@@ -105,7 +127,7 @@ pub fn spawn_amulet_of_yala(
 
     tilemap
         .insert_tile(Tile {
-            point: (position.x - CAMERA_OFFSET_X, position.y - CAMERA_OFFSET_Y),
+            point: map_spec.tilemap_pos(position),
             sprite_index,
             sprite_order,
             tint: Color::WHITE,
@@ -113,7 +135,18 @@ pub fn spawn_amulet_of_yala(
         .unwrap();
 }
 
-pub fn spawn_hud(commands: &mut Commands, font_handle: Handle<Font>) {
+/// Help line shown at the bottom of the screen, naming the keys moving the player.
+pub fn info_text(input_map: &InputMap) -> String {
+    format!(
+        "Explore the Dungeon. {}/{}/{}/{} to move.",
+        input_map.key_label(PlayerAction::MoveNorth),
+        input_map.key_label(PlayerAction::MoveWest),
+        input_map.key_label(PlayerAction::MoveSouth),
+        input_map.key_label(PlayerAction::MoveEast),
+    )
+}
+
+pub fn spawn_hud(commands: &mut Commands, font_handle: Handle<Font>, input_map: &InputMap) {
     let alignment = TextAlignment {
         vertical: VerticalAlign::Bottom,
         horizontal: HorizontalAlign::Center,
@@ -150,7 +183,7 @@ pub fn spawn_hud(commands: &mut Commands, font_handle: Handle<Font>) {
             parent
                 .spawn_bundle(Text2dBundle {
                     text: Text::with_section(
-                        "Explore the Dungeon. Cursor keys to move.",
+                        info_text(input_map),
                         TextStyle {
                             font: font_handle.clone(),
                             font_size: 10.0,
@@ -186,7 +219,10 @@ pub fn spawn_hud(commands: &mut Commands, font_handle: Handle<Font>) {
     commands
         .spawn_bundle(Text2dBundle {
             text: Text::with_section(
-                "Items carried",
+                format!(
+                    "Items carried ({} for details)",
+                    input_map.key_label(PlayerAction::Inventory)
+                ),
                 TextStyle {
                     font: font_handle.clone(),
                     font_size: 10.0,
@@ -205,9 +241,114 @@ pub fn spawn_hud(commands: &mut Commands, font_handle: Handle<Font>) {
         })
         .insert(Hud)
         .insert(InventoryText);
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text {
+                sections: Vec::new(),
+                alignment: TextAlignment {
+                    vertical: VerticalAlign::Bottom,
+                    horizontal: HorizontalAlign::Right,
+                },
+            },
+            ..Default::default()
+        })
+        .insert(Hud)
+        .insert(LogText);
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text {
+                sections: Vec::new(),
+                alignment,
+            },
+            visible: Visible {
+                is_visible: false,
+                is_transparent: false,
+            },
+            ..Default::default()
+        })
+        .insert(Hud)
+        .insert(LogOverlay::default());
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text {
+                sections: Vec::new(),
+                alignment,
+            },
+            visible: Visible {
+                is_visible: false,
+                is_transparent: false,
+            },
+            ..Default::default()
+        })
+        .insert(Hud)
+        .insert(InventoryOverlay);
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text {
+                sections: Vec::new(),
+                alignment,
+            },
+            visible: Visible {
+                is_visible: false,
+                is_transparent: false,
+            },
+            ..Default::default()
+        })
+        .insert(Hud)
+        .insert(LevelUpText);
+
+    let targeting_cursor = GeometryBuilder::build_as(
+        &shapes::Rectangle {
+            width: 32.,
+            height: 32.,
+            origin: shapes::RectangleOrigin::BottomLeft,
+        },
+        ShapeColors::new(Color::YELLOW),
+        DrawMode::Stroke(StrokeOptions::default().with_line_width(2.)),
+        Transform::default(),
+    );
+
+    commands
+        .spawn_bundle(targeting_cursor)
+        .insert(Visible {
+            is_visible: false,
+            is_transparent: true,
+        })
+        .insert(Hud)
+        .insert(TargetingCursor);
 }
 
-pub fn spawn_end_game_screens(commands: &mut Commands, font_handle: Handle<Font>) {
+pub fn inventory_text(font_handle: Handle<Font>) -> Text2dBundle {
+    Text2dBundle {
+        text: Text::with_section(
+            String::default(),
+            TextStyle {
+                font: font_handle,
+                font_size: 10.0,
+                color: Color::WHITE,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Bottom,
+                horizontal: HorizontalAlign::Right,
+            },
+        ),
+        ..Default::default()
+    }
+}
+
+pub fn spawn_end_game_screens(
+    commands: &mut Commands,
+    font_handle: Handle<Font>,
+    input_map: &InputMap,
+) {
+    let play_again = format!(
+        "Press {} to play again.",
+        input_map.key_label(PlayerAction::Restart)
+    );
     let alignment = TextAlignment {
         vertical: VerticalAlign::Bottom,
         horizontal: HorizontalAlign::Center,
@@ -274,7 +415,7 @@ pub fn spawn_end_game_screens(commands: &mut Commands, font_handle: Handle<Font>
             parent
                 .spawn_bundle(Text2dBundle {
                     text: Text::with_section(
-                        "Press 1 to play again.",
+                        play_again.clone(),
                         TextStyle {
                             font: font_handle.clone(),
                             font_size: 10.0,
@@ -362,7 +503,7 @@ pub fn spawn_end_game_screens(commands: &mut Commands, font_handle: Handle<Font>
             parent
                 .spawn_bundle(Text2dBundle {
                     text: Text::with_section(
-                        "Press 1 to play again.",
+                        play_again.clone(),
                         TextStyle {
                             font: font_handle.clone(),
                             font_size: 10.0,
@@ -17,23 +17,64 @@ pub struct Template {
     pub levels: HashSet<usize>,
     pub frequency: i32,
     pub name: String,
+    pub description: Option<String>,
     pub glyph: char,
     pub sprite_order: usize,
     pub provides: Option<Vec<(String, i32)>>,
     pub hp: Option<i32>,
-    pub base_damage: Option<i32>,
+    pub base_damage: Option<Dice>,
+    pub accuracy: Option<i32>,
+    pub evasion: Option<i32>,
+    pub base_defense: Option<i32>,
+    pub speed: Option<i32>,
+    pub xp: Option<i32>,
+    pub loot: Option<Vec<(String, i32)>>,
+    pub range: Option<i32>,
+    pub inflicts: Option<Vec<(StatusEffect, i32)>>,
+    pub slot: Option<EquipmentSlot>,
 }
 
 #[derive(Clone, Deserialize, Debug, PartialEq)]
 pub enum EntityType {
     Enemy,
     Item,
+    Armor,
+    Trap,
+}
+
+impl EntityType {
+    /// Whether entities of this type can be picked up and carried.
+    pub fn is_item(&self) -> bool {
+        matches!(self, EntityType::Item | EntityType::Armor)
+    }
 }
 
 impl Templates {
     pub fn load() -> Self {
         let file = File::open("assets/template.ron").expect("Failed opening file");
-        ron::de::from_reader(file).expect("Unable to load templates")
+        let templates: Self = ron::de::from_reader(file).expect("Unable to load templates");
+        templates.check_loot_tables();
+        templates
+    }
+
+    fn item(&self, name: &str) -> Option<&Template> {
+        self.entities
+            .iter()
+            .find(|t| t.entity_type.is_item() && t.name == name)
+    }
+
+    /// Warns about loot tables naming something else than an item template.
+    fn check_loot_tables(&self) {
+        for template in self.entities.iter() {
+            for (name, _) in template.loot.iter().flatten() {
+                if self.item(name).is_none() {
+                    println!(
+                        "Warning: the loot of the {} names {}, which isn't an item template",
+                        template.name, name
+                    );
+                }
+            }
+        }
     }
 
     pub fn spawn_entities(
@@ -42,6 +83,8 @@ impl Templates {
         commands: &mut Commands,
         spawn_points: &[Point],
         tilemap: &mut Tilemap,
+        map_spec: &MapSpec,
+        rng: &mut GameRng,
     ) {
         let mut available_entities = Vec::new();
         self.entities
@@ -53,20 +96,82 @@ impl Templates {
                 }
             });
 
-        let mut rng = rand::thread_rng();
         spawn_points.iter().for_each(|position| {
-            if let Some(entity_spec) = available_entities.as_slice().choose(&mut rng) {
-                self.spawn_entity(commands, position, entity_spec, tilemap);
+            if let Some(entity_spec) = available_entities.as_slice().choose(rng) {
+                self.spawn_entity(commands, position, entity_spec, tilemap, map_spec);
+            }
+        });
+    }
+
+    pub fn spawn_prefab_entities(
+        &self,
+        level: usize,
+        commands: &mut Commands,
+        prefab_spawns: &[(Point, PrefabSpawn)],
+        tilemap: &mut Tilemap,
+        map_spec: &MapSpec,
+        rng: &mut GameRng,
+    ) {
+        let mut available_enemies = Vec::new();
+        let mut available_items = Vec::new();
+        self.entities
+            .iter()
+            .filter(|e| e.levels.contains(&level))
+            .for_each(|t| {
+                for _ in 0..t.frequency {
+                    match t.entity_type {
+                        EntityType::Enemy => available_enemies.push(t),
+                        EntityType::Item | EntityType::Armor => available_items.push(t),
+                        EntityType::Trap => (),
+                    }
+                }
+            });
+
+        prefab_spawns.iter().for_each(|(position, spawn)| {
+            let entity_spec = match spawn {
+                PrefabSpawn::Monster => available_enemies.as_slice().choose(rng).copied(),
+                PrefabSpawn::Item => available_items.as_slice().choose(rng).copied(),
+                PrefabSpawn::Template(name) => {
+                    let template = self.entities.iter().find(|t| &t.name == name);
+                    if template.is_none() {
+                        println!("Warning: we don't know any template named {}", name);
+                    }
+                    template
+                }
+            };
+
+            if let Some(entity_spec) = entity_spec {
+                self.spawn_entity(commands, position, entity_spec, tilemap, map_spec);
             }
         });
     }
 
+    /// Spawns the item a killed enemy drops, returns whether it is an item template. Unknown
+    /// names were reported when loading the templates.
+    pub fn spawn_loot(
+        &self,
+        commands: &mut Commands,
+        position: &Point,
+        name: &str,
+        tilemap: &mut Tilemap,
+        map_spec: &MapSpec,
+    ) -> bool {
+        match self.item(name) {
+            Some(template) => {
+                self.spawn_entity(commands, position, template, tilemap, map_spec);
+                true
+            }
+            None => false,
+        }
+    }
+
     fn spawn_entity(
         &self,
         commands: &mut Commands,
         position: &Point,
         template: &Template,
         tilemap: &mut Tilemap,
+        map_spec: &MapSpec,
     ) {
         let sprite_index = to_cp437(template.glyph);
         let sprite_order = template.sprite_order;
@@ -80,14 +185,32 @@ impl Templates {
             })
             .insert(Name(template.name.clone()));
 
+        if let Some(description) = &template.description {
+            entity.insert(Description(description.clone()));
+        }
+
         match template.entity_type {
             EntityType::Item => {
                 entity.insert(Item);
             }
+            EntityType::Armor => {
+                entity.insert(Item);
+                entity.insert(Armor);
+            }
+            EntityType::Trap => {
+                entity.insert(Trap);
+            }
             EntityType::Enemy => {
                 entity.insert(Enemy);
                 entity.insert(FieldOfView::new(6));
                 entity.insert(ChasingPlayer);
+                entity.insert(template.speed.map_or_else(Speed::default, Speed));
+                entity.insert(Energy::default());
+                entity.insert(StatusEffects::default());
+                entity.insert(ExperienceValue(template.xp.unwrap_or(1)));
+                if let Some(loot) = &template.loot {
+                    entity.insert(LootTable(loot.clone()));
+                }
                 entity.insert(Health {
                     current: template.hp.unwrap(),
                     max: template.hp.unwrap(),
@@ -96,6 +219,7 @@ impl Templates {
         }
 
         if let Some(effects) = &template.provides {
+            let mut status_effects = Vec::new();
             effects
                 .iter()
                 .for_each(|(provides, n)| match provides.as_str() {
@@ -105,22 +229,51 @@ impl Templates {
                     "MagicMap" => {
                         entity.insert(ProvidesDungeonMap);
                     }
+                    "Regeneration" => {
+                        status_effects.push((StatusEffect::Regeneration, *n));
+                    }
                     _ => {
                         println!("Warning: we don't know how to provide {}", provides);
                     }
                 });
+            if !status_effects.is_empty() {
+                entity.insert(ProvidesEffects(status_effects));
+            }
+        }
+
+        if let Some(effects) = &template.inflicts {
+            entity.insert(InflictsEffects(effects.clone()));
         }
 
         if let Some(damage) = template.base_damage {
             entity.insert(Damage(damage));
             if template.entity_type == EntityType::Item {
-                entity.insert(Weapon);
+                match template.range {
+                    Some(range) => entity.insert(RangedWeapon { range }),
+                    None => entity.insert(Weapon),
+                };
             }
         }
 
+        if let Some(slot) = template.slot {
+            entity.insert(Equippable(slot));
+        }
+
+        if let Some(defense) = template.base_defense {
+            entity.insert(Defense(defense));
+        }
+
+        if let Some(accuracy) = template.accuracy {
+            entity.insert(Accuracy(accuracy));
+        }
+
+        if let Some(evasion) = template.evasion {
+            entity.insert(Evasion(evasion));
+        }
+
         tilemap
             .insert_tile(Tile {
-                point: (position.x - CAMERA_OFFSET_X, position.y - CAMERA_OFFSET_Y),
+                point: map_spec.tilemap_pos(*position),
                 sprite_index,
                 sprite_order,
                 tint: Color::WHITE,
@@ -128,3 +281,9 @@ impl Templates {
             .unwrap();
     }
 }
+
+impl FromWorld for Templates {
+    fn from_world(_world: &mut World) -> Self {
+        Templates::load()
+    }
+}
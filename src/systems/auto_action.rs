@@ -0,0 +1,144 @@
+use crate::prelude::*;
+
+/// Distance past which unexplored tiles are out of reach.
+const EXPLORE_MAX_DEPTH: f32 = 1024.0;
+
+/// The map without its stairs, taking them being left to the player, so that exploring goes
+/// around them.
+struct StairlessMap<'a>(&'a MapSpec);
+
+impl BaseMap for StairlessMap<'_> {
+    fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
+        self.0
+            .get_available_exits(idx)
+            .into_iter()
+            .filter(|&(exit, _)| !self.0.tiles[exit].is_stairs())
+            .collect()
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        self.0.get_pathing_distance(idx1, idx2)
+    }
+}
+
+pub fn auto_action(
+    key_events: Res<Events<KeyboardInput>>,
+    mut key_reader: ResMut<ManualEventReader<KeyboardInput>>,
+    mut turn_state: ResMut<State<TurnState>>,
+    mut auto_action: ResMut<AutoAction>,
+    mut log: ResMut<GameLog>,
+    mut ev_movements: EventWriter<WantsToMove>,
+    map_spec: Res<MapSpec>,
+    mut player_query: Query<(Entity, &Point, &Health, &FieldOfView, &mut Energy), With<Player>>,
+    enemies_query: Query<&Point, With<Enemy>>,
+    items_query: Query<(&Point, &Name), (With<Item>, Without<Carried>)>,
+) {
+    if *auto_action == AutoAction::Idle {
+        return;
+    }
+
+    // Any key interrupts, without being handled by player_input
+    if key_reader
+        .iter(&key_events)
+        .any(|ev| ev.state == ElementState::Pressed)
+    {
+        log.add("You stop", Color::GRAY);
+        *auto_action = AutoAction::Idle;
+        return;
+    }
+
+    let (player, &player_pos, health, fov, mut energy) = player_query.single_mut().unwrap();
+
+    if enemies_query
+        .iter()
+        .any(|pos| fov.visible_tiles.contains(pos))
+    {
+        log.add("You stop, an enemy is in sight", Color::ORANGE);
+        *auto_action = AutoAction::Idle;
+        return;
+    }
+
+    match *auto_action {
+        AutoAction::Idle => (),
+        AutoAction::Rest => {
+            if health.current >= health.max {
+                log.add("You feel rested", Color::GREEN);
+                *auto_action = AutoAction::Idle;
+                return;
+            }
+        }
+        AutoAction::Explore => {
+            let map_spec = &*map_spec;
+            let stairless_map = StairlessMap(map_spec);
+
+            // What is in sight gets revealed on the next move, it's already explored
+            let search_targets: Vec<usize> = (0..map_spec.num_tiles())
+                .filter(|&idx| {
+                    let pos = map_spec.index_to_point2d(idx);
+                    !map_spec.revealed_tiles[idx]
+                        && !fov.visible_tiles.contains(&pos)
+                        && map_spec.can_enter_tile(pos)
+                        && !map_spec.tiles[idx].is_stairs()
+                })
+                .collect();
+            let dijkstra_map = DijkstraMap::new(
+                map_spec.width,
+                map_spec.height,
+                &search_targets,
+                &stairless_map,
+                EXPLORE_MAX_DEPTH,
+            );
+
+            // Unexplored pockets out of reach, or only reachable through stairs, leave the
+            // player at the max depth
+            let idx = map_spec.map_idx(player_pos.x, player_pos.y);
+            let destination =
+                match DijkstraMap::find_lowest_exit(&dijkstra_map, idx, &stairless_map) {
+                    Some(destination) if dijkstra_map.map[idx] < EXPLORE_MAX_DEPTH => {
+                        map_spec.index_to_point2d(destination)
+                    }
+                    _ => {
+                        log.add("There is nothing left to explore", Color::GRAY);
+                        *auto_action = AutoAction::Idle;
+                        return;
+                    }
+                };
+
+            ev_movements.send(WantsToMove {
+                entity: player,
+                destination,
+            });
+
+            // Stepping on an item ends the exploration, to pick it up or not
+            if let Some((_, name)) = items_query.iter().find(|(&pos, _)| pos == destination) {
+                log.add(
+                    format!("You stop, there is a {} here", name.0),
+                    Color::WHITE,
+                );
+                *auto_action = AutoAction::Idle;
+            }
+        }
+        AutoAction::Travel(ref mut path) => {
+            // A step that didn't happen, e.g. into a closed tile, leaves the player off the path
+            let destination = match path.first() {
+                Some(&destination) if map_spec.is_adjacent(player_pos, destination) => destination,
+                _ => {
+                    *auto_action = AutoAction::Idle;
+                    return;
+                }
+            };
+            path.remove(0);
+            if path.is_empty() {
+                *auto_action = AutoAction::Idle;
+            }
+
+            ev_movements.send(WantsToMove {
+                entity: player,
+                destination,
+            });
+        }
+    }
+
+    energy.0 -= Energy::ACTION_COST;
+    turn_state.set(TurnState::PlayerTurn).unwrap();
+}
@@ -0,0 +1,33 @@
+use crate::prelude::*;
+
+pub fn camera_follow(
+    map_spec: Res<MapSpec>,
+    player_query: Query<&Point, (With<Player>, Changed<Point>)>,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+) {
+    if let (Ok(player_pos), Ok(mut camera_transform)) =
+        (player_query.single(), camera_query.single_mut())
+    {
+        let (x, y) = map_spec.tilemap_pos(*player_pos);
+        camera_transform.translation.x = x as f32 * 32.;
+        camera_transform.translation.y = y as f32 * 32.;
+    }
+}
+
+pub fn tilemap_spawned(
+    windows: Res<Windows>,
+    tilemap_query: Query<Entity, Added<Tilemap>>,
+    mut ev_window: EventWriter<WindowResized>,
+) {
+    if tilemap_query.iter().next().is_none() {
+        return;
+    }
+
+    // Hacky fix for https://github.com/joshuajbouw/bevy_tilemap/issues/152
+    let window = windows.get_primary().unwrap();
+    ev_window.send(WindowResized {
+        id: window.id(),
+        width: window.width(),
+        height: window.height(),
+    });
+}
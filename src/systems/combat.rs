@@ -1,43 +1,213 @@
 use crate::prelude::*;
 
+const BASE_HIT_CHANCE: i32 = 80;
+const CRITICAL_CHANCE: i32 = 5;
+/// Hit chance lost for each tile between a ranged attacker and its victim.
+const RANGE_PENALTY: i32 = 5;
+
 pub fn combat(
     mut commands: Commands,
+    map_spec: Res<MapSpec>,
+    mut rng: ResMut<GameRng>,
     mut ev_attacks: ResMut<Events<WantsToAttack>>,
+    mut ev_combat: EventWriter<CombatEvent>,
     mut tilemap_query: Query<&mut Tilemap>,
-    mut victim_query: Query<(&mut Health, &Point, &Render)>,
+    mut victim_query: Query<(
+        &mut Health,
+        &Point,
+        &Render,
+        Option<&ExperienceValue>,
+        Option<&LootTable>,
+        Option<&mut StatusEffects>,
+    )>,
     attacker_query: Query<&Damage, Or<(With<Player>, With<Enemy>)>>,
-    weapon_query: Query<(&Damage, &Carried), With<Weapon>>,
-    player_query: Query<Entity, With<Player>>,
+    defense_query: Query<&Defense, Or<(With<Player>, With<Enemy>)>>,
+    equipment_query: Query<&Equipment>,
+    gear_query: Query<
+        (
+            Option<&Damage>,
+            Option<&Defense>,
+            Option<&Accuracy>,
+            Option<&Evasion>,
+            Option<&RangedWeapon>,
+        ),
+        With<Item>,
+    >,
+    inflicts_query: Query<&InflictsEffects>,
+    (accuracy_query, evasion_query): (Query<&Accuracy>, Query<&Evasion>),
+    names_query: Query<&Name, Without<Player>>,
+    mut player_query: Query<(Entity, &mut Experience), With<Player>>,
+    templates: Res<Templates>,
 ) {
     let mut tilemap = tilemap_query.single_mut().unwrap();
-    let player = player_query.single().unwrap();
+    let (player, mut experience) = player_query.single_mut().unwrap();
+
+    // Only equipped items add their bonuses
+    let equipped = |owner: Entity| {
+        equipment_query.get(owner).map_or_else(
+            |_| Vec::new(),
+            |equipment| {
+                equipment
+                    .items()
+                    .filter_map(|item| gear_query.get(item).ok())
+                    .collect::<Vec<_>>()
+            },
+        )
+    };
+
+    for WantsToAttack {
+        victim,
+        attacker,
+        kind,
+    } in ev_attacks.drain()
+    {
+        let (mut health, pos, render, experience_value, loot_table, status_effects) =
+            match victim_query.get_mut(victim) {
+                Ok(victim_components) => victim_components,
+                // Already killed this turn
+                Err(_) => continue,
+            };
+        if health.current < 1 {
+            continue;
+        }
+
+        // Resolve hit chance, 1 to CRITICAL_CHANCE always hits critically
+
+        let attacker_gear = equipped(attacker);
+        let victim_gear = equipped(victim);
+
+        let accuracy = accuracy_query.get(attacker).map_or(0, |&Accuracy(a)| a)
+            + attacker_gear
+                .iter()
+                .filter_map(|(_, _, accuracy, ..)| accuracy.map(|&Accuracy(a)| a))
+                .sum::<i32>();
+        let evasion = evasion_query.get(victim).map_or(0, |&Evasion(e)| e)
+            + victim_gear
+                .iter()
+                .filter_map(|(_, _, _, evasion, _)| evasion.map(|&Evasion(e)| e))
+                .sum::<i32>();
+        let range_penalty = match kind {
+            AttackKind::Melee => 0,
+            AttackKind::Ranged { distance } | AttackKind::Thrown { distance, .. } => {
+                RANGE_PENALTY * (distance - 1)
+            }
+        };
+        let hit_chance = i32::clamp(BASE_HIT_CHANCE + accuracy - evasion - range_penalty, 5, 95);
+
+        let roll = rng.gen_range(1..=100);
+        let critical = roll <= CRITICAL_CHANCE;
+
+        let outcome = if roll > hit_chance && !critical {
+            AttackOutcome::Miss
+        } else {
+            // Ranged attacks only deal the damage of the weapon they are fired with
+            let ranged = kind != AttackKind::Melee;
+
+            let base_damage = match ranged {
+                true => 0,
+                false => attacker_query
+                    .get(attacker)
+                    .map(|Damage(dice)| dice.roll(&mut *rng))
+                    .unwrap_or(0),
+            };
 
-    for WantsToAttack { victim, attacker } in ev_attacks.drain() {
-        let base_damage = attacker_query
-            .get(attacker)
-            .map(|&Damage(d)| d)
-            .unwrap_or(0);
+            // Thrown items hurt with their own damage, a single point for non weapons
+            let weapon_damage = match kind {
+                AttackKind::Thrown { item, .. } => gear_query
+                    .get(item)
+                    .ok()
+                    .and_then(|(damage, ..)| damage)
+                    .map_or(1, |Damage(dice)| dice.roll(&mut *rng)),
+                _ => attacker_gear
+                    .iter()
+                    .filter(|(.., ranged_weapon)| ranged_weapon.is_some() == ranged)
+                    .filter_map(|(damage, ..)| *damage)
+                    .map(|Damage(dice)| dice.roll(&mut *rng))
+                    .sum::<i32>(),
+            };
 
-        let weapon_damage = weapon_query
-            .iter()
-            .filter(|(_, &Carried(entity))| entity == attacker)
-            .map(|(&Damage(d), _)| d)
-            .sum::<i32>();
+            let base_defense = defense_query.get(victim).map(|&Defense(d)| d).unwrap_or(0);
 
-        let final_damage = base_damage + weapon_damage;
+            let armor_defense = victim_gear
+                .iter()
+                .filter_map(|(_, defense, ..)| defense.map(|&Defense(d)| d))
+                .sum::<i32>();
 
-        let (mut health, pos, render) = victim_query.get_mut(victim).unwrap();
+            let mut damage = base_damage + weapon_damage;
+            if critical {
+                damage *= 2;
+            }
+            let final_damage = i32::max(0, damage - base_defense - armor_defense);
+
+            health.current -= final_damage;
+            if critical {
+                AttackOutcome::Critical(final_damage)
+            } else {
+                AttackOutcome::Hit(final_damage)
+            }
+        };
+
+        let killed = health.current < 1;
+        let mut loot = None;
+
+        // Hits carry the attacker's status effects to survivors
+        let mut effects = Vec::new();
+        if let (false, Some(mut status_effects)) = (killed, status_effects) {
+            if let (AttackOutcome::Hit(_), Ok(InflictsEffects(inflicted)))
+            | (AttackOutcome::Critical(_), Ok(InflictsEffects(inflicted))) =
+                (outcome, inflicts_query.get(attacker))
+            {
+                for &(effect, turns) in inflicted.iter() {
+                    status_effects.add(effect, turns);
+                    effects.push(effect);
+                }
+            }
+        }
+
+        if killed && victim != player {
+            if attacker == player {
+                experience.current += experience_value.map_or(0, |&ExperienceValue(xp)| xp);
+            }
 
-        health.current -= final_damage;
-        if health.current < 1 && victim != player {
             commands.entity(victim).despawn();
 
             tilemap
-                .clear_tile(
-                    (pos.x - CAMERA_OFFSET_X, pos.y - CAMERA_OFFSET_Y),
-                    render.sprite_order,
-                )
+                .clear_tile(map_spec.tilemap_pos(*pos), render.sprite_order)
                 .unwrap();
+
+            if let Some(item) = loot_table.and_then(|loot| loot.roll(&mut *rng)) {
+                if spawn_loot(
+                    &templates,
+                    &mut commands,
+                    *pos,
+                    item,
+                    &mut tilemap,
+                    &map_spec,
+                ) {
+                    loot = Some(item.to_string());
+                }
+            }
         }
+
+        ev_combat.send(CombatEvent {
+            attacker: names_query.get(attacker).ok().map(|name| name.0.clone()),
+            victim: names_query.get(victim).ok().map(|name| name.0.clone()),
+            outcome,
+            killed,
+            loot,
+            effects,
+        });
+    }
+}
+
+pub fn log_combat(mut ev_combat: EventReader<CombatEvent>, mut log: ResMut<GameLog>) {
+    for event in ev_combat.iter() {
+        let color = match (event.outcome, &event.victim) {
+            (AttackOutcome::Miss, _) => Color::GRAY,
+            (AttackOutcome::Critical(_), _) => Color::YELLOW,
+            (_, None) => Color::RED,
+            _ => Color::WHITE,
+        };
+        log.add(event.message(), color);
     }
 }
@@ -10,6 +10,7 @@ pub fn despawn_level(
         Or<(
             (With<Item>, Without<Carried>),
             With<Enemy>,
+            With<Trap>,
             With<Hud>,
             With<Tilemap>,
         )>,
@@ -21,82 +22,143 @@ pub fn despawn_level(
     turn_state.set(TurnState::AwaitingInput).unwrap();
 }
 
+/// Keeps the level being left in the dungeon so it can be restored later.
+pub fn store_level(world: &mut World) {
+    let depth = world
+        .query::<&Player>()
+        .iter(world)
+        .next()
+        .unwrap()
+        .map_level;
+    let level = StoredLevel::capture(world);
+
+    world
+        .get_resource_or_insert_with(Dungeon::default)
+        .levels
+        .insert(depth, level);
+}
+
 pub fn respawn_level(
-    windows: Res<Windows>,
     font_handle: Res<Handle<Font>>,
     texture_atlas_handle: Res<Handle<TextureAtlas>>,
+    turn_state: Res<State<TurnState>>,
+    mut dungeon: ResMut<Dungeon>,
+    mut log: ResMut<GameLog>,
     mut commands: Commands,
-    mut camera_query: Query<&mut Transform, With<Camera>>,
     mut player_query: Query<(&mut Player, &mut Point, &mut FieldOfView)>,
-    mut ev_window: EventWriter<WindowResized>,
+    mut rng: ResMut<GameRng>,
+    movement: Res<Movement>,
+    mut auto_action: ResMut<AutoAction>,
+    input_map: Res<InputMap>,
+    templates: Res<Templates>,
+    prefabs: Res<Prefabs>,
 ) {
-    // Build new map
+    let (mut player, mut player_pos, mut player_fov) = player_query.single_mut().unwrap();
+    *auto_action = AutoAction::Idle;
+
+    // The state being exited tells whether we went down or up
+    let going_down = turn_state.current() == &TurnState::NextLevel;
+    if going_down {
+        player.map_level += 1;
+        log.add(
+            format!("You descend to dungeon level {}", player.map_level + 1),
+            Color::YELLOW,
+        );
+    } else {
+        // end_turn only climbs up from below the first level
+        player.map_level -= 1;
+        log.add(
+            format!("You climb back to dungeon level {}", player.map_level + 1),
+            Color::YELLOW,
+        );
+    }
 
-    let (mut tilemap, map_builder) = make_tilemap(texture_atlas_handle.clone());
-    let MapBuilder {
-        player_start,
-        amulet_start,
-        monster_spawns,
-        mut map_spec,
-        theme,
-        ..
-    } = map_builder;
+    let (mut tilemap, map_spec) = if let Some(level) = dungeon.levels.remove(&player.map_level) {
+        // Restore visited level
+
+        let mut tilemap = build_tilemap(texture_atlas_handle.clone(), &level.map_spec);
+        level.restore(&mut commands, &mut tilemap, &font_handle);
+
+        let stairs = if going_down {
+            TileType::UpStairs
+        } else {
+            TileType::Exit
+        };
+        let stairs_pos = match level.map_spec.find_tile(stairs) {
+            Some(stairs_pos) => stairs_pos,
+            None => {
+                println!("Warning: the stored level has no stairs to arrive by");
+                Point::new(level.map_spec.width / 2, level.map_spec.height / 2)
+            }
+        };
+        let occupied = level
+            .entities
+            .iter()
+            .filter_map(|saved| saved.position.map(|(x, y)| Point::new(x, y)))
+            .collect::<Vec<_>>();
+        *player_pos = level.map_spec.next_to(stairs_pos, &occupied);
+
+        (tilemap, level.map_spec)
+    } else {
+        // Build new map
+
+        let (mut tilemap, map_builder) = make_tilemap(
+            texture_atlas_handle.clone(),
+            player.map_level as usize,
+            *movement,
+            &prefabs,
+            &mut rng,
+        );
+        let MapBuilder {
+            player_start,
+            amulet_start,
+            monster_spawns,
+            prefab_spawns,
+            mut map_spec,
+            ..
+        } = map_builder;
+
+        let spawns = monster_spawns
+            .iter()
+            .copied()
+            .chain(prefab_spawns.iter().map(|(pos, _)| *pos))
+            .chain(std::iter::once(amulet_start))
+            .collect::<Vec<_>>();
+        *player_pos = map_spec.next_to(player_start, &spawns);
+
+        if player.map_level == 2 {
+            spawn_amulet_of_yala(&mut commands, amulet_start, &mut tilemap, &mut map_spec);
+        }
+        spawn_level(
+            &templates,
+            player.map_level as usize,
+            &mut commands,
+            &monster_spawns,
+            &prefab_spawns,
+            &mut tilemap,
+            &map_spec,
+            &mut rng,
+        );
 
-    // Reset player
+        (tilemap, map_spec)
+    };
 
-    let (mut player, mut player_pos, mut player_fov) = player_query.single_mut().unwrap();
+    // Reset player
 
     player_fov.is_dirty = true;
-    *player_pos = player_start;
-    player.map_level += 1;
 
     tilemap
         .insert_tile(Tile {
-            point: (
-                player_pos.x - CAMERA_OFFSET_X,
-                player_pos.y - CAMERA_OFFSET_Y,
-            ),
+            point: map_spec.tilemap_pos(*player_pos),
             sprite_index: to_cp437('@'),
             sprite_order: 3,
             tint: Color::WHITE,
         })
         .unwrap();
 
-    // Respawn entities
-
-    if player.map_level == 2 {
-        spawn_amulet_of_yala(
-            &mut commands,
-            amulet_start,
-            &mut tilemap,
-            &mut map_spec,
-            theme.as_ref(),
-        );
-    }
     commands.insert_resource(map_spec);
-
-    spawn_level(
-        player.map_level as usize,
-        &mut commands,
-        &monster_spawns,
-        &mut tilemap,
-    );
-    spawn_hud(&mut commands, font_handle.clone());
+    spawn_hud(&mut commands, font_handle.clone(), &input_map);
     spawn_tilemap(&mut commands, tilemap);
-
-    // Reset camera
-
-    let mut camera_transform = camera_query.single_mut().unwrap();
-    camera_transform.translation.x = (player_start.x as f32 - CAMERA_OFFSET_X as f32) * 32.;
-    camera_transform.translation.y = (player_start.y as f32 - CAMERA_OFFSET_Y as f32) * 32.;
-
-    // Hacky fix for https://github.com/joshuajbouw/bevy_tilemap/issues/152
-    let window = windows.get_primary().unwrap();
-    ev_window.send(WindowResized {
-        id: window.id(),
-        width: window.width(),
-        height: window.height(),
-    });
 }
 
 pub fn despawn_game_state(
@@ -107,6 +169,7 @@ pub fn despawn_game_state(
             With<Player>,
             With<Item>,
             With<Enemy>,
+            With<Trap>,
             With<Hud>,
             With<Tilemap>,
         )>,
@@ -118,13 +181,18 @@ pub fn despawn_game_state(
 }
 
 pub fn respawn_game_state(
-    windows: Res<Windows>,
     font_handle: Res<Handle<Font>>,
     texture_atlas_handle: Res<Handle<TextureAtlas>>,
     mut commands: Commands,
     mut texts_query: Query<&mut Visible, Or<(With<VictoryText>, With<GameoverText>)>>,
-    mut camera_query: Query<&mut Transform, With<Camera>>,
-    mut ev_window: EventWriter<WindowResized>,
+    mut dungeon: ResMut<Dungeon>,
+    mut log: ResMut<GameLog>,
+    mut rng: ResMut<GameRng>,
+    movement: Res<Movement>,
+    mut auto_action: ResMut<AutoAction>,
+    input_map: Res<InputMap>,
+    templates: Res<Templates>,
+    prefabs: Res<Prefabs>,
 ) {
     // Remove victory/gameover screen
 
@@ -132,43 +200,50 @@ pub fn respawn_game_state(
         text.is_visible = false;
     }
 
-    // Build new map
-
-    let (mut tilemap, map_builder) = make_tilemap(texture_atlas_handle.clone());
+    // Build new map, a new game gets a new seed and forgets visited levels
+
+    rng.reseed();
+    dungeon.levels.clear();
+    log.entries.clear();
+    *auto_action = AutoAction::Idle;
+    let (mut tilemap, map_builder) = make_tilemap(
+        texture_atlas_handle.clone(),
+        0,
+        *movement,
+        &prefabs,
+        &mut rng,
+    );
     let MapBuilder {
         player_start,
         monster_spawns,
+        prefab_spawns,
         map_spec,
         ..
     } = map_builder;
 
-    commands.insert_resource(map_spec);
-
     // Respawn entities
 
-    spawn_player(&mut commands, player_start, &mut tilemap);
-    spawn_level(0, &mut commands, &monster_spawns, &mut tilemap);
-    spawn_hud(&mut commands, font_handle.clone());
+    spawn_player(&mut commands, player_start, &mut tilemap, &map_spec);
+    spawn_level(
+        &templates,
+        0,
+        &mut commands,
+        &monster_spawns,
+        &prefab_spawns,
+        &mut tilemap,
+        &map_spec,
+        &mut rng,
+    );
+    commands.insert_resource(map_spec);
+    spawn_hud(&mut commands, font_handle.clone(), &input_map);
     spawn_tilemap(&mut commands, tilemap);
-
-    // Reset camera
-
-    let mut camera_transform = camera_query.single_mut().unwrap();
-    camera_transform.translation.x = (player_start.x as f32 - CAMERA_OFFSET_X as f32) * 32.;
-    camera_transform.translation.y = (player_start.y as f32 - CAMERA_OFFSET_Y as f32) * 32.;
-
-    // Hacky fix for https://github.com/joshuajbouw/bevy_tilemap/issues/152
-    let window = windows.get_primary().unwrap();
-    ev_window.send(WindowResized {
-        id: window.id(),
-        width: window.width(),
-        height: window.height(),
-    });
 }
 
 pub fn text_screen<T>(
     mut turn_state: ResMut<State<TurnState>>,
-    mut key_evr: EventReader<KeyboardInput>,
+    key_events: Res<Events<KeyboardInput>>,
+    mut key_reader: ResMut<ManualEventReader<KeyboardInput>>,
+    input_map: Res<InputMap>,
     mut q: QuerySet<(
         Query<(&Transform, &OrthographicProjection), With<Camera>>,
         Query<(&T, &mut Transform, &Text2dSize, &mut Visible)>,
@@ -205,9 +280,11 @@ pub fn text_screen<T>(
 
     // Handle user input
 
-    for ev in key_evr.iter().take(1) {
+    for ev in key_reader.iter(&key_events).take(1) {
         match (ev.state, ev.key_code) {
-            (ElementState::Pressed, Some(KeyCode::Key1)) => {
+            (ElementState::Pressed, Some(key))
+                if input_map.is_bound(key, PlayerAction::Restart) =>
+            {
                 turn_state.set(TurnState::AwaitingInput).unwrap()
             }
             _ => (),
@@ -1,20 +1,81 @@
+use std::ops::DerefMut;
+
 use crate::prelude::*;
 
+/// Clock ticks without enemies in sight needed to recover a health point.
+const REGENERATION_TURNS: u32 = 10;
+
 pub fn end_turn(
+    mut commands: Commands,
     map_spec: Res<MapSpec>,
     mut turn_state: ResMut<State<TurnState>>,
-    player_query: Query<(&Point, &Health), With<Player>>,
+    mut log: ResMut<GameLog>,
+    mut tilemap_query: Query<&mut Tilemap>,
+    mut player_query: Query<(Entity, &mut Player, &Point, &FieldOfView, &Experience)>,
+    enemies_query: Query<&Point, With<Enemy>>,
     amulet_query: Query<&Point, With<AmuletOfYala>>,
+    mut actors_query: Query<(Entity, &mut Energy, &Speed)>,
+    mut effects_query: Query<(
+        Entity,
+        &mut StatusEffects,
+        &mut Health,
+        &Point,
+        &Render,
+        Option<&Name>,
+    )>,
 ) {
+    let (player, mut player_state, player_pos, fov, experience) =
+        player_query.single_mut().unwrap();
+    let mut tilemap = tilemap_query.single_mut().unwrap();
+
     let current_state = turn_state.current().clone();
     let mut new_state = match current_state {
         TurnState::AwaitingInput => return,
-        TurnState::PlayerTurn => TurnState::MonsterTurn,
-        TurnState::MonsterTurn => TurnState::AwaitingInput,
+        TurnState::PlayerTurn => {
+            if experience.can_level_up() {
+                TurnState::LevelUp
+            } else {
+                TurnState::MonsterTurn
+            }
+        }
+        TurnState::MonsterTurn => {
+            let mut actors: Vec<_> = actors_query.iter_mut().collect();
+            let (next_state, ticks) = next_actor(player, &mut actors);
+
+            // Effects follow the clock rather than the player's actions, which may be quick
+            for _ in 0..ticks {
+                tick_status_effects(
+                    &mut commands,
+                    &map_spec,
+                    &mut log,
+                    &mut tilemap,
+                    &mut effects_query,
+                );
+                regenerate(
+                    player,
+                    &mut player_state,
+                    fov,
+                    &enemies_query,
+                    &mut effects_query,
+                );
+            }
+            next_state
+        }
         _ => current_state,
     };
 
-    let (player_pos, player_hp) = player_query.single().unwrap();
+    // A stunned player loses its turn, letting monsters act again
+    let player_stunned = effects_query
+        .get_mut(player)
+        .is_ok_and(|(_, effects, ..)| effects.has(StatusEffect::Stun));
+    if new_state == TurnState::AwaitingInput && player_stunned {
+        let (_, mut energy, _) = actors_query.get_mut(player).unwrap();
+        energy.0 -= Energy::ACTION_COST;
+        log.add("You are stunned and lose your turn", Color::ORANGE);
+        new_state = TurnState::MonsterTurn;
+    }
+
+    let (_, _, player_hp, ..) = effects_query.get_mut(player).unwrap();
     if player_hp.current < 1 {
         new_state = TurnState::GameOver;
     }
@@ -25,12 +86,193 @@ pub fn end_turn(
         new_state = TurnState::Victory;
     }
 
-    let idx = map_idx(player_pos.x, player_pos.y);
-    if map_spec.tiles[idx] == TileType::Exit {
-        new_state = TurnState::NextLevel;
+    let idx = map_spec.map_idx(player_pos.x, player_pos.y);
+    match map_spec.tiles[idx] {
+        TileType::Exit => new_state = TurnState::NextLevel,
+        // The first level has nothing above it to climb back to
+        TileType::UpStairs if player_state.map_level > 0 => new_state = TurnState::PreviousLevel,
+        _ => (),
     }
 
     if &new_state != turn_state.current() {
         turn_state.set(new_state).unwrap();
     }
 }
+
+/// Lets time pass until someone can act, the player going first when several actors are ready.
+/// Also tells how many times the clock ticked.
+fn next_actor<E: DerefMut<Target = Energy>>(
+    player: Entity,
+    actors: &mut [(Entity, E, &Speed)],
+) -> (TurnState, u32) {
+    let mut ticks = 0;
+    loop {
+        if actors
+            .iter()
+            .any(|(actor, energy, _)| *actor == player && energy.is_ready())
+        {
+            return (TurnState::AwaitingInput, ticks);
+        }
+
+        // Monsters still ready get another go, fast ones act several times per player turn
+        if actors
+            .iter()
+            .any(|(actor, energy, _)| *actor != player && energy.is_ready())
+        {
+            return (TurnState::MonsterTurn, ticks);
+        }
+
+        actors
+            .iter_mut()
+            .for_each(|(_, energy, speed)| energy.0 += speed.0);
+        ticks += 1;
+    }
+}
+
+/// Slowly heals the player while no enemy is in sight.
+fn regenerate(
+    player: Entity,
+    player_state: &mut Player,
+    fov: &FieldOfView,
+    enemies_query: &Query<&Point, With<Enemy>>,
+    effects_query: &mut Query<(
+        Entity,
+        &mut StatusEffects,
+        &mut Health,
+        &Point,
+        &Render,
+        Option<&Name>,
+    )>,
+) {
+    if enemies_query
+        .iter()
+        .any(|pos| fov.visible_tiles.contains(pos))
+    {
+        player_state.calm_turns = 0;
+        return;
+    }
+
+    player_state.calm_turns += 1;
+    if player_state.calm_turns >= REGENERATION_TURNS {
+        player_state.calm_turns = 0;
+        if let Ok((_, _, mut health, ..)) = effects_query.get_mut(player) {
+            if health.current > 0 {
+                health.current = i32::min(health.max, health.current + 1);
+            }
+        }
+    }
+}
+
+/// Applies status effects for one tick of the clock and wears them off, monsters succumbing
+/// to poison are removed.
+fn tick_status_effects(
+    commands: &mut Commands,
+    map_spec: &MapSpec,
+    log: &mut GameLog,
+    tilemap: &mut Tilemap,
+    effects_query: &mut Query<(
+        Entity,
+        &mut StatusEffects,
+        &mut Health,
+        &Point,
+        &Render,
+        Option<&Name>,
+    )>,
+) {
+    for (entity, mut effects, mut health, pos, render, name) in effects_query.iter_mut() {
+        if health.current < 1 || effects.0.is_empty() {
+            continue;
+        }
+
+        for (effect, turns) in effects.0.iter_mut() {
+            match effect {
+                StatusEffect::Poison => health.current -= 1,
+                StatusEffect::Regeneration => {
+                    health.current = i32::min(health.max, health.current + 1)
+                }
+                StatusEffect::Confusion | StatusEffect::Stun => (),
+            }
+            *turns -= 1;
+        }
+
+        // Only the player is told about its own effects wearing off
+        effects.0.retain(|&(effect, turns)| {
+            if turns < 1 && name.is_none() {
+                log.add(
+                    format!("You are no longer {}", effect.name().to_lowercase()),
+                    Color::GRAY,
+                );
+            }
+            turns > 0
+        });
+
+        if health.current < 1 {
+            match name {
+                Some(name) => {
+                    log.add(format!("The {} succumbs to poison", name.0), Color::WHITE);
+                    commands.entity(entity).despawn();
+                    tilemap
+                        .clear_tile(map_spec.tilemap_pos(*pos), render.sprite_order)
+                        .unwrap();
+                }
+                None => log.add("You succumb to poison", Color::RED),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the scheduler for a number of clock ticks, the player spending `player_cost` on
+    /// each of its actions, and counts the actions of each actor, the player first.
+    fn count_actions(speeds: &[i32], player_cost: i32, ticks: u32) -> Vec<u32> {
+        let player = Entity::new(0);
+        let speeds: Vec<_> = speeds.iter().map(|&speed| Speed(speed)).collect();
+        let mut energies = vec![Energy::default(); speeds.len()];
+        let mut actions = vec![0; speeds.len()];
+
+        let mut elapsed = 0;
+        loop {
+            let mut actors: Vec<_> = energies
+                .iter_mut()
+                .zip(speeds.iter())
+                .enumerate()
+                .map(|(id, (energy, speed))| (Entity::new(id as u32), energy, speed))
+                .collect();
+            let (state, passed) = next_actor(player, &mut actors);
+            elapsed += passed;
+            if elapsed >= ticks {
+                return actions;
+            }
+
+            if state == TurnState::AwaitingInput {
+                actions[0] += 1;
+                energies[0].0 -= player_cost;
+            } else {
+                for (id, energy) in energies.iter_mut().enumerate().skip(1) {
+                    if energy.is_ready() {
+                        actions[id] += 1;
+                        energy.0 -= Energy::ACTION_COST;
+                    }
+                }
+            }
+        }
+    }
+
+    // Actors start with the energy of a single action, so those acting twice per tick only
+    // act once on the first one.
+
+    #[test]
+    fn speed_sets_how_often_actors_act() {
+        let actions = count_actions(&[100, 50, 100, 200], Energy::ACTION_COST, 100);
+        assert_eq!(actions, vec![100, 50, 100, 199]);
+    }
+
+    #[test]
+    fn quick_actions_cost_half_a_turn() {
+        let actions = count_actions(&[100, 100], Energy::QUICK_ACTION_COST, 100);
+        assert_eq!(actions, vec![199, 100]);
+    }
+}
@@ -0,0 +1,9 @@
+use crate::prelude::*;
+
+/// Monsters that were ready this turn just acted, chasing or moving randomly.
+pub fn spend_energy(mut monsters_query: Query<&mut Energy, With<Enemy>>) {
+    monsters_query
+        .iter_mut()
+        .filter(|energy| energy.is_ready())
+        .for_each(|mut energy| energy.0 -= Energy::ACTION_COST);
+}
@@ -1,9 +1,26 @@
+use super::inventory_stacks;
 use crate::prelude::*;
 
+const LOG_LINES: usize = 5;
+const LOG_OVERLAY_LINES: usize = 30;
+/// Stacks listed in the HUD, one per number key.
+const INVENTORY_HUD_STACKS: usize = 9;
+const INVENTORY_SCREEN_LINES: usize = 20;
+
 pub fn main_hud(
     windows: Res<Windows>,
     mut commands: Commands,
-    health_query: Query<&Health, With<Player>>,
+    player_query: Query<
+        (
+            &Health,
+            &Experience,
+            &StatusEffects,
+            &Equipment,
+            Option<&Defense>,
+        ),
+        With<Player>,
+    >,
+    gear_query: Query<&Defense, With<Item>>,
     mut q: QuerySet<(
         Query<(&Transform, &OrthographicProjection), With<Camera>>,
         Query<(&mut Transform, &Text2dSize, &mut Text), (With<Hud>, With<HealthText>)>,
@@ -19,15 +36,43 @@ pub fn main_hud(
     let proj_bottom = proj.bottom;
     let camera_translation = camera_transform.translation;
 
-    // Player health query
+    // Player health and armor query
 
-    let player_health = health_query.single().unwrap();
+    let (player_health, experience, status_effects, equipment, player_defense) =
+        player_query.single().unwrap();
+    let armor = player_defense.map_or(0, |&Defense(d)| d)
+        + equipment
+            .items()
+            .filter_map(|item| gear_query.get(item).ok())
+            .map(|&Defense(d)| d)
+            .sum::<i32>();
 
     // HealthText query (parent)
 
     let (mut transform, size, mut text) = q.q1_mut().single_mut().unwrap();
 
-    text.sections[0].value = format!("Health: {} / {}", player_health.current, player_health.max);
+    text.sections[0].value = format!(
+        "Health: {} / {}  Armor: {}  Level: {} (XP {} / {})",
+        player_health.current,
+        player_health.max,
+        armor,
+        experience.level,
+        experience.current,
+        experience.next_level()
+    );
+
+    // Active status effects, each one in its own color
+    text.sections.truncate(1);
+    let style = text.sections[0].style.clone();
+    for &(effect, turns) in status_effects.0.iter() {
+        text.sections.push(TextSection {
+            value: format!("  {} ({})", effect.name(), turns),
+            style: TextStyle {
+                color: effect.color(),
+                ..style.clone()
+            },
+        });
+    }
 
     transform.translation.x = camera_translation.x;
     transform.translation.y = camera_translation.y - proj_bottom;
@@ -62,7 +107,7 @@ pub fn main_hud(
 
 pub fn inventory_hud(
     windows: Res<Windows>,
-    player_query: Query<Entity, With<Player>>,
+    player_query: Query<(Entity, &Equipment), With<Player>>,
     offset_query: Query<
         &Text2dSize,
         (
@@ -74,7 +119,19 @@ pub fn inventory_hud(
     mut q: QuerySet<(
         Query<(&Transform, &OrthographicProjection), With<Camera>>,
         Query<(&mut Transform, &Text2dSize, &mut Visible), (With<Hud>, With<InventoryText>)>,
-        Query<(&Carried, &Name, &mut Transform, &Text2dSize, &mut Text), With<Item>>,
+        Query<
+            (
+                Entity,
+                &Carried,
+                &Name,
+                &mut Transform,
+                &Text2dSize,
+                &mut Text,
+                &mut Visible,
+                Option<&Equippable>,
+            ),
+            With<Item>,
+        >,
     )>,
 ) {
     let window = windows.get_primary().unwrap();
@@ -103,33 +160,45 @@ pub fn inventory_hud(
 
     y_offset += size.size.height;
 
-    // Display carried items
+    // Display carried items, a single line per stack
 
-    let player = player_query.single().unwrap();
-    let mut nb_carried = 0;
-    q.q2_mut()
-        .iter_mut()
-        .filter(|(carried, ..)| carried.0 == player)
-        .enumerate()
-        .for_each(|(i, (_, name, mut transform, size, mut text))| {
-            text.sections[0].value = format!("{} : {}", i + 1, name.0);
+    let (player, equipment) = player_query.single().unwrap();
+    let stacks = inventory_stacks(
+        q.q2_mut()
+            .iter_mut()
+            .filter(|(_, carried, ..)| carried.0 == player)
+            .map(|(item, _, name, .., equippable)| (item, name, equippable.is_none())),
+    );
+    for (i, stack) in stacks.iter().enumerate() {
+        for (j, &item) in stack.iter().enumerate() {
+            let (_, _, name, mut transform, size, mut text, mut visible, _) =
+                q.q2_mut().get_mut(item).unwrap();
+
+            // The other stacks are only listed in the inventory screen
+            visible.is_visible = j == 0 && i < INVENTORY_HUD_STACKS;
+            if !visible.is_visible {
+                continue;
+            }
+
+            text.sections[0].value = stack_label(i + 1, name, stack.len(), equipment.slot_of(item));
 
             transform.translation.x = camera_translation.x - x_offset;
             transform.translation.y = camera_translation.y - proj_bottom - y_offset;
             transform.translation.z = 999.0;
 
             y_offset += size.size.height;
-            nb_carried += 1;
-        });
+        }
+    }
 
     // Display inventory text
 
     let (_, _, mut visible) = q.q1_mut().single_mut().unwrap();
-    visible.is_visible = nb_carried > 0;
+    visible.is_visible = !stacks.is_empty();
 }
 
 pub fn level_hud(
     windows: Res<Windows>,
+    rng: Res<GameRng>,
     player_query: Query<&Player>,
     heath_text_query: Query<&Text2dSize, (With<Hud>, With<HealthText>)>,
     mut level_text_query: Query<
@@ -146,5 +215,360 @@ pub fn level_hud(
     let (mut transform, mut text, size) = level_text_query.single_mut().unwrap();
     transform.translation.x = (window.width() / 2.) - size.size.width;
     transform.translation.y = -health_height;
-    text.sections[0].value = format!("Dungeon Level: {}", map_level + 1);
+    text.sections[0].value = format!("Dungeon Level: {}  Seed: {}", map_level + 1, rng.seed());
+}
+
+pub fn log_hud(
+    windows: Res<Windows>,
+    log: Res<GameLog>,
+    font_handle: Res<Handle<Font>>,
+    mut q: QuerySet<(
+        Query<(&Transform, &OrthographicProjection), With<Camera>>,
+        Query<(&mut Transform, &Text2dSize, &mut Text), (With<Hud>, With<LogText>)>,
+    )>,
+) {
+    let window = windows.get_primary().unwrap();
+    let x_offset = (window.width() / 2.) - 16.;
+
+    // Camera query
+
+    let (camera_transform, proj) = q.q0().single().unwrap();
+    let proj_bottom = proj.bottom;
+    let camera_translation = camera_transform.translation;
+
+    // LogText query, the most recent messages at the bottom of the screen
+
+    let (mut transform, size, mut text) = q.q1_mut().single_mut().unwrap();
+    text.sections = log_sections(log.window(LOG_LINES, 0), &font_handle);
+
+    transform.translation.x = camera_translation.x - x_offset;
+    transform.translation.y = camera_translation.y + proj_bottom + size.size.height;
+    transform.translation.z = 999.0;
+}
+
+pub fn log_overlay(
+    log: Res<GameLog>,
+    font_handle: Res<Handle<Font>>,
+    input_map: Res<InputMap>,
+    mut turn_state: ResMut<State<TurnState>>,
+    key_events: Res<Events<KeyboardInput>>,
+    mut key_reader: ResMut<ManualEventReader<KeyboardInput>>,
+    mut q: QuerySet<(
+        Query<(&Transform, &OrthographicProjection), With<Camera>>,
+        Query<(&mut LogOverlay, &mut Transform, &mut Text, &mut Visible)>,
+    )>,
+) {
+    // Camera query
+
+    let (camera_transform, proj) = q.q0().single().unwrap();
+    let proj_bottom = proj.bottom;
+    let camera_translation = camera_transform.translation;
+
+    // Handle user input
+
+    let (mut overlay, mut transform, mut text, mut visible) = q.q1_mut().single_mut().unwrap();
+    let max_scroll = log.entries.len().saturating_sub(LOG_OVERLAY_LINES);
+
+    for ev in key_reader.iter(&key_events).take(1) {
+        let action = input_map.menu_action(
+            ev,
+            &[
+                PlayerAction::MoveNorth,
+                PlayerAction::MoveSouth,
+                PlayerAction::ScrollUp,
+                PlayerAction::ScrollDown,
+                PlayerAction::Cancel,
+                PlayerAction::MessageLog,
+            ],
+        );
+
+        match action {
+            Some(PlayerAction::MoveNorth) => {
+                overlay.scroll = usize::min(overlay.scroll + 1, max_scroll)
+            }
+            Some(PlayerAction::MoveSouth) => overlay.scroll = overlay.scroll.saturating_sub(1),
+            Some(PlayerAction::ScrollUp) => {
+                overlay.scroll = usize::min(overlay.scroll + LOG_OVERLAY_LINES, max_scroll)
+            }
+            Some(PlayerAction::ScrollDown) => {
+                overlay.scroll = overlay.scroll.saturating_sub(LOG_OVERLAY_LINES)
+            }
+            Some(PlayerAction::Cancel) | Some(PlayerAction::MessageLog) => {
+                overlay.scroll = 0;
+                visible.is_visible = false;
+                turn_state.set(TurnState::AwaitingInput).unwrap();
+                return;
+            }
+            _ => (),
+        }
+    }
+
+    // Display the history
+
+    let mut sections = vec![TextSection {
+        value: format!(
+            "Message log ({}/{}/{}/{} to scroll, {} to close)\n\n",
+            input_map.key_label(PlayerAction::MoveNorth),
+            input_map.key_label(PlayerAction::MoveSouth),
+            input_map.key_label(PlayerAction::ScrollUp),
+            input_map.key_label(PlayerAction::ScrollDown),
+            input_map.key_label(PlayerAction::MessageLog),
+        ),
+        style: TextStyle {
+            font: font_handle.clone(),
+            font_size: 10.0,
+            color: Color::YELLOW,
+        },
+    }];
+    sections.extend(log_sections(
+        log.window(LOG_OVERLAY_LINES, overlay.scroll),
+        &font_handle,
+    ));
+    text.sections = sections;
+
+    visible.is_visible = true;
+    transform.translation.x = camera_translation.x;
+    transform.translation.y = camera_translation.y - proj_bottom;
+    transform.translation.z = 999.0;
+}
+
+pub fn inventory_screen(
+    font_handle: Res<Handle<Font>>,
+    input_map: Res<InputMap>,
+    turn_state: Res<State<TurnState>>,
+    menu: Res<InventoryMenu>,
+    player_query: Query<(Entity, &Equipment), With<Player>>,
+    items_query: Query<
+        (
+            Entity,
+            &Carried,
+            &Name,
+            Option<&Description>,
+            Option<&Equippable>,
+        ),
+        With<Item>,
+    >,
+    mut q: QuerySet<(
+        Query<(&Transform, &OrthographicProjection), With<Camera>>,
+        Query<(&mut Transform, &mut Text, &mut Visible), (With<Hud>, With<InventoryOverlay>)>,
+    )>,
+) {
+    // Camera query
+
+    let (camera_transform, proj) = q.q0().single().unwrap();
+    let proj_bottom = proj.bottom;
+    let camera_translation = camera_transform.translation;
+
+    // InventoryOverlay query, hidden once the inventory is closed
+
+    let (mut transform, mut text, mut visible) = q.q1_mut().single_mut().unwrap();
+    if turn_state.current() != &TurnState::Inventory {
+        visible.is_visible = false;
+        return;
+    }
+
+    let (player, equipment) = player_query.single().unwrap();
+    let stacks = inventory_stacks(
+        items_query
+            .iter()
+            .filter(|(_, carried, ..)| carried.0 == player)
+            .map(|(item, _, name, _, equippable)| (item, name, equippable.is_none())),
+    );
+
+    let style = |color| TextStyle {
+        font: font_handle.clone(),
+        font_size: 10.0,
+        color,
+    };
+
+    let mut sections = vec![TextSection {
+        value: format!(
+            "Inventory ({}/{} to select, {} to use, {} to equip, {} to drop, {} to throw, \
+            {} to close)\n\n",
+            input_map.key_label(PlayerAction::MoveNorth),
+            input_map.key_label(PlayerAction::MoveSouth),
+            input_map.key_label(PlayerAction::Confirm),
+            input_map.key_label(PlayerAction::Equip),
+            input_map.key_label(PlayerAction::Drop),
+            input_map.key_label(PlayerAction::Throw),
+            input_map.key_label(PlayerAction::Cancel),
+        ),
+        style: style(Color::YELLOW),
+    }];
+
+    // Scroll the list so that the selected stack stays in view
+    let first = menu.selected.saturating_sub(INVENTORY_SCREEN_LINES - 1);
+    sections.extend(
+        stacks
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(INVENTORY_SCREEN_LINES)
+            .map(|(i, stack)| {
+                let (item, _, name, ..) = items_query.get(stack[0]).unwrap();
+                let label = stack_label(i + 1, name, stack.len(), equipment.slot_of(item));
+                match i == menu.selected {
+                    true => TextSection {
+                        value: format!("> {}\n", label),
+                        style: style(Color::GOLD),
+                    },
+                    false => TextSection {
+                        value: format!("  {}\n", label),
+                        style: style(Color::WHITE),
+                    },
+                }
+            }),
+    );
+
+    if let Some(stack) = stacks.get(menu.selected) {
+        let (.., description, _) = items_query.get(stack[0]).unwrap();
+        sections.push(TextSection {
+            value: format!(
+                "\n{}",
+                description.map_or("Nothing special about it.", |d| d.0.as_str())
+            ),
+            style: style(Color::GRAY),
+        });
+    }
+    text.sections = sections;
+
+    visible.is_visible = true;
+    transform.translation.x = camera_translation.x;
+    transform.translation.y = camera_translation.y - proj_bottom;
+    transform.translation.z = 999.0;
+}
+
+/// Inventory line of a stack of `count` items, the first of them being in `slot`.
+fn stack_label(number: usize, name: &Name, count: usize, slot: Option<EquipmentSlot>) -> String {
+    let mut label = format!("{} : {}", number, name.0);
+    if count > 1 {
+        label.push_str(&format!(" (x{})", count));
+    }
+    if let Some(slot) = slot {
+        label.push_str(&format!(" ({})", slot.name()));
+    }
+    label
+}
+
+pub fn level_up_hud(
+    font_handle: Res<Handle<Font>>,
+    input_map: Res<InputMap>,
+    turn_state: Res<State<TurnState>>,
+    player_query: Query<&Experience, With<Player>>,
+    mut q: QuerySet<(
+        Query<(&Transform, &OrthographicProjection), With<Camera>>,
+        Query<(&mut Transform, &mut Text, &mut Visible), (With<Hud>, With<LevelUpText>)>,
+    )>,
+) {
+    // Camera query
+
+    let (camera_transform, proj) = q.q0().single().unwrap();
+    let proj_bottom = proj.bottom;
+    let camera_translation = camera_transform.translation;
+
+    // LevelUpText query, hidden as soon as a choice has been made
+
+    let (mut transform, mut text, mut visible) = q.q1_mut().single_mut().unwrap();
+    if turn_state.current() != &TurnState::LevelUp {
+        visible.is_visible = false;
+        return;
+    }
+
+    let experience = player_query.single().unwrap();
+    let style = |color| TextStyle {
+        font: font_handle.clone(),
+        font_size: 10.0,
+        color,
+    };
+
+    let mut sections = vec![TextSection {
+        value: format!(
+            "You reach character level {}! Choose an improvement:\n\n",
+            experience.level + 1
+        ),
+        style: style(Color::YELLOW),
+    }];
+    sections.extend(
+        LevelUpChoice::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, choice)| TextSection {
+                value: format!(
+                    "{} : {}\n",
+                    input_map.key_label(PlayerAction::Choose(i)),
+                    choice.description()
+                ),
+                style: style(Color::WHITE),
+            }),
+    );
+    text.sections = sections;
+
+    visible.is_visible = true;
+    transform.translation.x = camera_translation.x;
+    transform.translation.y = camera_translation.y - proj_bottom / 2.;
+    transform.translation.z = 999.0;
+}
+
+pub fn targeting_hud(
+    map_spec: Res<MapSpec>,
+    input_map: Res<InputMap>,
+    targeting: Res<Targeting>,
+    turn_state: Res<State<TurnState>>,
+    enemies_query: Query<(&Point, &Name), With<Enemy>>,
+    mut cursor_query: Query<(&mut Transform, &mut Visible), (With<Hud>, With<TargetingCursor>)>,
+    mut info_query: Query<&mut Text, (With<Hud>, With<InfoText>)>,
+) {
+    let (mut transform, mut visible) = cursor_query.single_mut().unwrap();
+    let mut info = info_query.single_mut().unwrap();
+
+    let cursor = match (turn_state.current(), targeting.cursor) {
+        (TurnState::Targeting, Some(cursor)) => cursor,
+        _ => {
+            visible.is_visible = false;
+            info.sections[0].value = info_text(&input_map);
+            return;
+        }
+    };
+
+    let (x, y) = map_spec.tilemap_pos(cursor);
+    transform.translation.x = x as f32 * 32.;
+    transform.translation.y = y as f32 * 32.;
+    transform.translation.z = 999.0;
+    visible.is_visible = true;
+
+    let target = enemies_query
+        .iter()
+        .find(|(&pos, _)| pos == cursor)
+        .map_or("nothing".to_string(), |(_, name)| format!("the {}", name.0));
+    let action = match targeting.thrown {
+        Some(_) => "throw",
+        None => "fire",
+    };
+    info.sections[0].value = format!(
+        "Aiming at {}. {} to cycle, {} or click to {}, {} to cancel.",
+        target,
+        input_map.key_label(PlayerAction::NextTarget),
+        input_map.key_label(PlayerAction::Confirm),
+        action,
+        input_map.key_label(PlayerAction::Cancel),
+    );
+}
+
+fn log_sections(entries: &[LogEntry], font_handle: &Handle<Font>) -> Vec<TextSection> {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| TextSection {
+            value: if i + 1 < entries.len() {
+                format!("{}\n", entry.text)
+            } else {
+                entry.text.clone()
+            },
+            style: TextStyle {
+                font: font_handle.clone(),
+                font_size: 10.0,
+                color: entry.color,
+            },
+        })
+        .collect()
 }
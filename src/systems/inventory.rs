@@ -0,0 +1,133 @@
+use super::drop_item;
+use crate::prelude::*;
+
+pub fn inventory(
+    key_events: Res<Events<KeyboardInput>>,
+    input_map: Res<InputMap>,
+    mut key_reader: ResMut<ManualEventReader<KeyboardInput>>,
+    mut turn_state: ResMut<State<TurnState>>,
+    mut menu: ResMut<InventoryMenu>,
+    mut targeting: ResMut<Targeting>,
+    mut ev_item: EventWriter<ActivateItem>,
+    mut log: ResMut<GameLog>,
+    mut commands: Commands,
+    map_spec: Res<MapSpec>,
+    mut tilemap_query: Query<&mut Tilemap>,
+    mut player_query: Query<(Entity, &Point, &mut Energy, &mut Equipment), With<Player>>,
+    items_query: Query<(Entity, &Carried, &Render, &Name, Option<&Equippable>), With<Item>>,
+) {
+    let (player, &player_pos, mut energy, mut equipment) = player_query.single_mut().unwrap();
+
+    let stacks = inventory_stacks(
+        items_query
+            .iter()
+            .filter(|(_, carried, ..)| carried.0 == player)
+            .map(|(item, _, _, name, equippable)| (item, name, equippable.is_none())),
+    );
+
+    if stacks.is_empty() {
+        log.add("You carry nothing", Color::GRAY);
+        turn_state.set(TurnState::AwaitingInput).unwrap();
+        return;
+    }
+    menu.selected = usize::min(menu.selected, stacks.len() - 1);
+
+    for ev in key_reader.iter(&key_events).take(1) {
+        let item = stacks[menu.selected][0];
+        let (_, _, render, name, equippable) = items_query.get(item).unwrap();
+
+        let action = input_map.menu_action(
+            ev,
+            &[
+                PlayerAction::MoveNorth,
+                PlayerAction::MoveSouth,
+                PlayerAction::Confirm,
+                PlayerAction::Equip,
+                PlayerAction::Drop,
+                PlayerAction::Throw,
+                PlayerAction::Cancel,
+                PlayerAction::Inventory,
+            ],
+        );
+
+        let cost = match action {
+            Some(PlayerAction::MoveNorth) => {
+                menu.selected = (menu.selected + stacks.len() - 1) % stacks.len();
+                return;
+            }
+            Some(PlayerAction::MoveSouth) => {
+                menu.selected = (menu.selected + 1) % stacks.len();
+                return;
+            }
+
+            // use the item, equippable ones are toggled in and out of their slot
+            Some(PlayerAction::Confirm) => {
+                ev_item.send(ActivateItem {
+                    used_by: player,
+                    item,
+                });
+                Energy::QUICK_ACTION_COST
+            }
+            Some(PlayerAction::Equip) => {
+                if equippable.is_none() {
+                    log.add(format!("You can't equip the {}", name.0), Color::GRAY);
+                    return;
+                }
+                ev_item.send(ActivateItem {
+                    used_by: player,
+                    item,
+                });
+                Energy::QUICK_ACTION_COST
+            }
+
+            Some(PlayerAction::Drop) => {
+                log.add(format!("You drop the {}", name.0), Color::WHITE);
+                let mut tilemap = tilemap_query.single_mut().unwrap();
+                drop_item(
+                    &mut commands,
+                    &mut tilemap,
+                    &map_spec,
+                    &mut equipment,
+                    item,
+                    render,
+                    player_pos,
+                );
+                Energy::ACTION_COST
+            }
+
+            // the targeting system throws the item and ends the turn
+            Some(PlayerAction::Throw) => {
+                targeting.thrown = Some(item);
+                turn_state.set(TurnState::Targeting).unwrap();
+                return;
+            }
+
+            Some(PlayerAction::Cancel) | Some(PlayerAction::Inventory) => {
+                turn_state.set(TurnState::AwaitingInput).unwrap();
+                return;
+            }
+            _ => return,
+        };
+
+        energy.0 -= cost;
+        turn_state.set(TurnState::PlayerTurn).unwrap();
+    }
+}
+
+/// Groups carried items in inventory order, identical items sharing a stack when they are
+/// stackable (consumables, not equipment).
+pub fn inventory_stacks<'a>(
+    items: impl Iterator<Item = (Entity, &'a Name, bool)>,
+) -> Vec<Vec<Entity>> {
+    let mut stacks: Vec<(&Name, bool, Vec<Entity>)> = Vec::new();
+    for (item, name, stackable) in items {
+        match stacks.iter_mut().find(|(stack_name, stack_stackable, _)| {
+            stackable && *stack_stackable && stack_name.0 == name.0
+        }) {
+            Some((.., stack)) => stack.push(item),
+            None => stacks.push((name, stackable, vec![item])),
+        }
+    }
+
+    stacks.into_iter().map(|(.., stack)| stack).collect()
+}
@@ -0,0 +1,51 @@
+use crate::prelude::*;
+
+pub fn level_up(
+    key_events: Res<Events<KeyboardInput>>,
+    input_map: Res<InputMap>,
+    mut key_reader: ResMut<ManualEventReader<KeyboardInput>>,
+    mut turn_state: ResMut<State<TurnState>>,
+    mut log: ResMut<GameLog>,
+    mut player_query: Query<
+        (&mut Experience, &mut Health, &mut Damage, &mut FieldOfView),
+        With<Player>,
+    >,
+) {
+    for ev in key_reader.iter(&key_events).take(1) {
+        let choice = match (0..LevelUpChoice::ALL.len())
+            .find(|&i| input_map.pressed(ev, PlayerAction::Choose(i)))
+        {
+            Some(i) => LevelUpChoice::ALL[i],
+            None => return,
+        };
+
+        let (mut experience, mut health, mut damage, mut fov) = player_query.single_mut().unwrap();
+
+        match choice {
+            LevelUpChoice::MaxHealth => {
+                health.max += 5;
+                health.current += 5;
+            }
+            LevelUpChoice::Damage => damage.0.bonus += 1,
+            LevelUpChoice::FieldOfView => {
+                *fov = FieldOfView::new(fov.radius + 1);
+            }
+        }
+
+        experience.current -= experience.next_level();
+        experience.level += 1;
+        log.add(
+            format!(
+                "You reach character level {} ({})",
+                experience.level,
+                choice.description()
+            ),
+            Color::GREEN,
+        );
+
+        // Several levels may have been gained at once
+        if !experience.can_level_up() {
+            turn_state.set(TurnState::MonsterTurn).unwrap();
+        }
+    }
+}
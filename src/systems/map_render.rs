@@ -6,7 +6,7 @@ pub fn map_render(
     mut tilemap_query: Query<&mut Tilemap>,
     player_query: Query<(&Point, &FieldOfView), With<Player>>,
     enemies_query: Query<(&Point, &Render), With<Enemy>>,
-    items_query: Query<(&Point, &Render), With<Item>>,
+    objects_query: Query<(&Point, &Render), Or<(With<Item>, With<Trap>)>>,
     camera_query: Query<&Transform, With<Camera>>,
 ) {
     let window = windows.get_primary().unwrap();
@@ -16,9 +16,10 @@ pub fn map_render(
     let camera_transform = camera_query.single().unwrap();
     let world_pos = camera_transform.compute_matrix() * p.extend(0.0).extend(1.0);
 
-    let x_min = (world_pos.x / 32.).floor() as i32 + CAMERA_OFFSET_X;
+    let offset = map_spec.tilemap_offset();
+    let x_min = (world_pos.x / 32.).floor() as i32 + offset.x;
     let x_max = x_min + (window_size.x / 32.).ceil() as i32;
-    let y_min = (world_pos.y / 32.).floor() as i32 + CAMERA_OFFSET_Y;
+    let y_min = (world_pos.y / 32.).floor() as i32 + offset.y;
     let y_max = y_min + (window_size.y / 32.).ceil() as i32;
 
     let mut tilemap = tilemap_query.single_mut().unwrap();
@@ -27,8 +28,8 @@ pub fn map_render(
     for y in y_min..=y_max {
         for x in x_min..=x_max {
             let pt = Point { x, y };
-            let idx = map_idx(x, y);
-            let tilemap_pos = (x - CAMERA_OFFSET_X, y - CAMERA_OFFSET_Y);
+            let idx = map_spec.map_idx(x, y);
+            let tilemap_pos = map_spec.tilemap_pos(pt);
 
             if map_spec.in_bounds(pt) && player_fov.visible_tiles.contains(&pt) {
                 // background
@@ -41,10 +42,10 @@ pub fn map_render(
                     tile.index = render.sprite_index;
                 }
 
-                // item
+                // item or trap
                 match tilemap.get_tile_mut(tilemap_pos, 2) {
                     Some(tile) if pt != player_pos => {
-                        let (_, render) = items_query.iter().find(|&(&p, _)| p == pt).unwrap();
+                        let (_, render) = objects_query.iter().find(|&(&p, _)| p == pt).unwrap();
                         tile.index = render.sprite_index;
                     }
                     _ => (),
@@ -63,7 +64,7 @@ pub fn map_render(
                     tile.index = 0; // no sprite
                 }
 
-                // item
+                // item or trap
                 match tilemap.get_tile_mut(tilemap_pos, 2) {
                     Some(tile) if pt != player_pos => {
                         tile.index = 0; // no sprite
@@ -74,3 +75,65 @@ pub fn map_render(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::window::WindowId;
+
+    use super::*;
+
+    #[test]
+    fn draws_traps_in_sight() {
+        let mut world = World::default();
+
+        let mut windows = Windows::default();
+        windows.add(Window::new(
+            WindowId::primary(),
+            &WindowDescriptor::default(),
+            640,
+            480,
+            1.0,
+            None,
+        ));
+        world.insert_resource(windows);
+
+        let map_spec = MapSpec::new(Point::new(10, 10));
+        let trap_pos = Point::new(5, 6);
+        let trap_sprite = to_cp437('^');
+        let tilemap_pos = map_spec.tilemap_pos(trap_pos);
+        let mut tilemap = build_tilemap(Handle::default(), &map_spec);
+        tilemap
+            .insert_tile(Tile {
+                point: tilemap_pos,
+                sprite_index: 0,
+                sprite_order: 2,
+                tint: Color::WHITE,
+            })
+            .unwrap();
+        let tilemap = world.spawn().insert(tilemap).id();
+        world.insert_resource(map_spec);
+
+        let mut fov = FieldOfView::new(6);
+        fov.visible_tiles.insert(trap_pos);
+        world
+            .spawn()
+            .insert(Player::default())
+            .insert(Point::new(5, 5))
+            .insert(fov);
+        world.spawn().insert(Trap).insert(trap_pos).insert(Render {
+            sprite_index: trap_sprite,
+            sprite_order: 2,
+        });
+        world
+            .spawn()
+            .insert(Camera::default())
+            .insert(Transform::default());
+
+        let mut stage = SystemStage::single(map_render.system());
+        stage.run(&mut world);
+
+        let mut tilemap = world.get_mut::<Tilemap>(tilemap).unwrap();
+        let tile = tilemap.get_tile(tilemap_pos, 2).unwrap();
+        assert_eq!(tile.index, trap_sprite);
+    }
+}
@@ -1,31 +1,54 @@
 use crate::prelude::*;
 
+mod auto_action;
+mod camera;
 mod chasing;
 mod combat;
 mod end_game;
 mod end_turn;
+mod energy;
 mod fov;
 mod hud;
+mod inventory;
+mod level_up;
 mod map_render;
+mod mouse_input;
 mod movement;
 mod player_input;
 mod random_move;
+mod save_load;
+mod setup;
+mod targeting;
 mod tooltips;
 mod use_items;
 
+use auto_action::*;
+use camera::*;
 use chasing::*;
 use combat::*;
 use end_game::*;
 use end_turn::*;
+use energy::*;
 use fov::*;
 use hud::*;
+use inventory::*;
+use level_up::*;
 use map_render::*;
+use mouse_input::*;
 use movement::*;
 use player_input::*;
 use random_move::*;
+use save_load::*;
+pub use setup::*;
+use targeting::*;
 use tooltips::*;
 use use_items::*;
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+enum SetupStage {
+    BuildMap,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
 enum GameStage {
     PlayerTurn,
@@ -38,112 +61,255 @@ enum TooltipStage {
     Display,
 }
 
-pub fn add_systems(app: &mut AppBuilder) -> &mut AppBuilder {
-    // Setup game stages
-
-    app.add_stage(GameStage::PlayerTurn, SystemStage::parallel());
-    app.add_stage_after(
-        GameStage::PlayerTurn,
-        GameStage::MonsterTurn,
-        SystemStage::parallel(),
-    );
-
-    app.add_system_set_to_stage(GameStage::PlayerTurn, State::<TurnState>::get_driver());
-    app.add_system_set_to_stage(GameStage::MonsterTurn, State::<TurnState>::get_driver());
-
-    // Setup tooltip stages
-
-    app.add_stage_before(
-        CoreStage::PostUpdate,
-        TooltipStage::Display,
-        SystemStage::parallel(),
-    );
-    app.add_stage_before(
-        TooltipStage::Display,
-        TooltipStage::Create,
-        SystemStage::parallel(),
-    );
-
-    // Game systems
-
-    app.stage(CoreStage::Startup, |schedule: &mut Schedule| {
-        schedule.add_system_to_stage(StartupStage::PostStartup, fov.system())
-    });
-
-    app.add_system_set(
-        SystemSet::on_update(TurnState::AwaitingInput)
-            .with_system(player_input.system())
-            .with_system(map_render.system())
-            .with_system(main_hud.system())
-            .with_system(inventory_hud.system())
-            .with_system(level_hud.system()),
-    );
-
-    app.add_system_set_to_stage(
-        GameStage::PlayerTurn,
-        SystemSet::on_update(TurnState::PlayerTurn)
-            .with_system(use_items.system())
-            .with_system(combat.system().label("combat"))
-            .with_system(movement.system().label("movement").after("combat"))
-            .with_system(end_turn.system().after("movement")),
-    );
-
-    app.add_system_set_to_stage(
-        GameStage::MonsterTurn,
-        SystemSet::on_update(TurnState::MonsterTurn)
-            .with_system(random_move.system().label("random_move"))
-            .with_system(chasing.system().label("chasing"))
-            .with_system(combat.system().label("combat").after("chasing"))
-            .with_system(movement.system().label("movement").after("combat"))
-            .with_system(end_turn.system().after("movement")),
-    );
-
-    app.add_system_set_to_stage(
-        GameStage::MonsterTurn,
-        SystemSet::on_enter(TurnState::AwaitingInput).with_system(fov.system()),
-    );
-
-    // Next level systems
-
-    app.add_system_set_to_stage(
-        GameStage::MonsterTurn,
-        SystemSet::on_enter(TurnState::NextLevel).with_system(despawn_level.system()),
-    );
-    app.add_system_set_to_stage(
-        GameStage::MonsterTurn,
-        SystemSet::on_exit(TurnState::NextLevel).with_system(respawn_level.system()),
-    );
-
-    // Victory systems
-
-    app.add_system_set_to_stage(
-        GameStage::MonsterTurn,
-        SystemSet::on_enter(TurnState::Victory).with_system(despawn_game_state.system()),
-    );
-    app.add_system_set(
-        SystemSet::on_update(TurnState::Victory).with_system(text_screen::<VictoryText>.system()),
-    );
-    app.add_system_set(
-        SystemSet::on_exit(TurnState::Victory).with_system(respawn_game_state.system()),
-    );
-
-    // Gameover systems
-
-    app.add_system_set_to_stage(
-        GameStage::MonsterTurn,
-        SystemSet::on_enter(TurnState::GameOver).with_system(despawn_game_state.system()),
-    );
-    app.add_system_set(
-        SystemSet::on_update(TurnState::GameOver).with_system(text_screen::<GameoverText>.system()),
-    );
-    app.add_system_set(
-        SystemSet::on_exit(TurnState::GameOver).with_system(respawn_game_state.system()),
-    );
-
-    // Tooltips systems
-
-    app.add_system_to_stage(TooltipStage::Create, tooltips.system());
-    app.add_system_to_stage(TooltipStage::Display, tooltips_display.system());
-
-    app
+/// Turn state machine: player input, inventory, ranged targeting, player and monster turns,
+/// field of view, level ups, save/load.
+///
+/// Keyboard events are read through a single `ManualEventReader` resource shared by the
+/// systems of every state, so that the key leaving a state isn't handled again by the next one.
+///
+/// Who acts next is decided by the energy each actor gains according to its `Speed`, the
+/// monster turn being repeated while some monsters are still ready to act.
+///
+/// Must be added before `MapPlugin` and `SpawnerPlugin` as it creates the game stages.
+pub struct TurnPlugin;
+
+impl Plugin for TurnPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_state(TurnState::AwaitingInput)
+            .init_resource::<GameRng>()
+            .init_resource::<Movement>()
+            .init_resource::<Handle<Font>>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<InputMap>()
+            .init_resource::<Templates>()
+            .init_resource::<ManualEventReader<KeyboardInput>>()
+            .init_resource::<Events<WantsToMove>>()
+            .init_resource::<Events<WantsToAttack>>()
+            .init_resource::<Events<WantsToSaveOrLoad>>()
+            .init_resource::<Events<ActivateItem>>()
+            .init_resource::<GameLog>()
+            .init_resource::<Targeting>()
+            .init_resource::<InventoryMenu>()
+            .init_resource::<AutoAction>()
+            .init_resource::<Prompt>()
+            .add_event::<CombatEvent>();
+
+        // Setup game stages
+
+        app.add_stage(GameStage::PlayerTurn, SystemStage::parallel());
+        app.add_stage_after(
+            GameStage::PlayerTurn,
+            GameStage::MonsterTurn,
+            SystemStage::parallel(),
+        );
+
+        app.add_system_set_to_stage(GameStage::PlayerTurn, State::<TurnState>::get_driver());
+        app.add_system_set_to_stage(GameStage::MonsterTurn, State::<TurnState>::get_driver());
+
+        // Game systems
+
+        app.add_system(log_combat.system());
+
+        app.stage(CoreStage::Startup, |schedule: &mut Schedule| {
+            schedule.add_system_to_stage(StartupStage::PostStartup, fov.system())
+        });
+
+        app.add_system_set(
+            SystemSet::on_update(TurnState::AwaitingInput)
+                .with_system(mouse_input.system().before("auto_action"))
+                .with_system(
+                    auto_action
+                        .system()
+                        .label("auto_action")
+                        .before("player_input"),
+                )
+                .with_system(player_input.system().label("player_input"))
+                .with_system(save_game.exclusive_system().at_end())
+                .with_system(load_game.system().after("player_input")),
+        );
+        app.add_system_set(
+            SystemSet::on_exit(TurnState::AwaitingInput).with_system(clear_prompt.system()),
+        );
+
+        app.add_system_set(SystemSet::on_update(TurnState::LevelUp).with_system(level_up.system()));
+        app.add_system_set(
+            SystemSet::on_update(TurnState::Targeting).with_system(targeting.system()),
+        );
+        app.add_system_set(
+            SystemSet::on_update(TurnState::Inventory).with_system(inventory.system()),
+        );
+
+        app.add_system_set_to_stage(
+            GameStage::PlayerTurn,
+            SystemSet::on_update(TurnState::PlayerTurn)
+                .with_system(use_items.system())
+                .with_system(combat.system().label("combat"))
+                .with_system(movement.system().label("movement").after("combat"))
+                .with_system(end_turn.system().after("movement")),
+        );
+
+        app.add_system_set_to_stage(
+            GameStage::MonsterTurn,
+            SystemSet::on_update(TurnState::MonsterTurn)
+                .with_system(fov.system().label("fov"))
+                .with_system(random_move.system().label("random_move"))
+                .with_system(chasing.system().label("chasing").after("fov"))
+                .with_system(
+                    spend_energy
+                        .system()
+                        .label("spend_energy")
+                        .after("random_move")
+                        .after("chasing"),
+                )
+                .with_system(combat.system().label("combat").after("chasing"))
+                .with_system(movement.system().label("movement").after("combat"))
+                .with_system(end_turn.system().after("movement").after("spend_energy")),
+        );
+
+        app.add_system_set_to_stage(
+            GameStage::MonsterTurn,
+            SystemSet::on_enter(TurnState::AwaitingInput).with_system(fov.system()),
+        );
+    }
+}
+
+/// Builds the first map at startup, then builds or restores levels when taking stairs.
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<GameRng>()
+            .init_resource::<Dungeon>()
+            .init_resource::<Prefabs>()
+            .init_resource::<Handle<TextureAtlas>>();
+
+        app.add_startup_stage_before(
+            StartupStage::Startup,
+            SetupStage::BuildMap,
+            SystemStage::parallel(),
+        );
+        app.add_startup_system_to_stage(SetupStage::BuildMap, setup_map.system());
+
+        // Level change systems
+
+        for level_change in [TurnState::NextLevel, TurnState::PreviousLevel].iter() {
+            app.add_system_set_to_stage(
+                GameStage::MonsterTurn,
+                SystemSet::on_enter(level_change.clone())
+                    .with_system(store_level.exclusive_system().at_start())
+                    .with_system(despawn_level.system()),
+            );
+            app.add_system_set_to_stage(
+                GameStage::MonsterTurn,
+                SystemSet::on_exit(level_change.clone()).with_system(respawn_level.system()),
+            );
+        }
+    }
+}
+
+/// Spawns the player and the level entities, and starts a new game after a victory or a
+/// game over.
+pub struct SpawnerPlugin;
+
+impl Plugin for SpawnerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_entities.system());
+
+        // Victory systems
+
+        app.add_system_set_to_stage(
+            GameStage::MonsterTurn,
+            SystemSet::on_enter(TurnState::Victory).with_system(despawn_game_state.system()),
+        );
+        app.add_system_set(
+            SystemSet::on_exit(TurnState::Victory).with_system(respawn_game_state.system()),
+        );
+
+        // Gameover systems
+
+        app.add_system_set_to_stage(
+            GameStage::MonsterTurn,
+            SystemSet::on_enter(TurnState::GameOver).with_system(despawn_game_state.system()),
+        );
+        // Headless games also end from the update stage, once their script is over. A state
+        // change is handled in the stage asking for it.
+        app.add_system_set(
+            SystemSet::on_enter(TurnState::GameOver).with_system(despawn_game_state.system()),
+        );
+        app.add_system_set(
+            SystemSet::on_exit(TurnState::GameOver).with_system(respawn_game_state.system()),
+        );
+    }
+}
+
+/// Health, level, inventory and message log display, tooltips and end game screens.
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_plugin(ShapePlugin);
+        app.add_startup_system(setup_hud.system());
+
+        // Setup tooltip stages
+
+        app.add_stage_before(
+            CoreStage::PostUpdate,
+            TooltipStage::Display,
+            SystemStage::parallel(),
+        );
+        app.add_stage_before(
+            TooltipStage::Display,
+            TooltipStage::Create,
+            SystemStage::parallel(),
+        );
+
+        // Hud systems
+
+        app.add_system_set(
+            SystemSet::on_update(TurnState::AwaitingInput)
+                .with_system(main_hud.system())
+                .with_system(inventory_hud.system())
+                .with_system(level_hud.system())
+                .with_system(log_hud.system()),
+        );
+        app.add_system_set(
+            SystemSet::on_update(TurnState::ShowingLog).with_system(log_overlay.system()),
+        );
+        app.add_system(level_up_hud.system());
+        app.add_system(targeting_hud.system());
+        app.add_system(inventory_screen.system());
+
+        // End game screens
+
+        app.add_system_set(
+            SystemSet::on_update(TurnState::Victory)
+                .with_system(text_screen::<VictoryText>.system()),
+        );
+        app.add_system_set(
+            SystemSet::on_update(TurnState::GameOver)
+                .with_system(text_screen::<GameoverText>.system()),
+        );
+
+        // Tooltips systems
+
+        app.add_system_to_stage(TooltipStage::Create, tooltips.system());
+        app.add_system_to_stage(TooltipStage::Display, tooltips_display.system());
+    }
+}
+
+/// Textures, font, camera and tilemap rendering.
+pub struct RenderPlugin;
+
+impl Plugin for RenderPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_plugins(TilemapDefaultPlugins);
+        app.add_startup_system_to_stage(StartupStage::PreStartup, setup_assets.system());
+
+        app.add_system_set(
+            SystemSet::on_update(TurnState::AwaitingInput).with_system(map_render.system()),
+        );
+
+        app.add_system(camera_follow.system());
+        app.add_system(tilemap_spawned.system());
+    }
 }
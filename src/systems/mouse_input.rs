@@ -0,0 +1,140 @@
+use std::collections::HashSet;
+
+use super::cursor_map_pos;
+use crate::prelude::*;
+
+/// The map as far as the player knows it, so that travelling doesn't give away the layout of
+/// unexplored places. Stairs are avoided unless they are where the player travels to.
+struct KnownMap<'a> {
+    map_spec: &'a MapSpec,
+    visible_tiles: &'a HashSet<Point>,
+    destination: usize,
+}
+
+impl KnownMap<'_> {
+    /// Tiles in sight are only marked revealed once the player moves.
+    fn is_known(&self, idx: usize) -> bool {
+        self.map_spec.revealed_tiles[idx]
+            || self
+                .visible_tiles
+                .contains(&self.map_spec.index_to_point2d(idx))
+    }
+}
+
+impl BaseMap for KnownMap<'_> {
+    fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
+        self.map_spec
+            .get_available_exits(idx)
+            .into_iter()
+            .filter(|&(exit, _)| {
+                self.is_known(exit)
+                    && (exit == self.destination || !self.map_spec.tiles[exit].is_stairs())
+            })
+            .collect()
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        self.map_spec.get_pathing_distance(idx1, idx2)
+    }
+}
+
+pub fn mouse_input(
+    key_events: Res<Events<KeyboardInput>>,
+    mut key_reader: ResMut<ManualEventReader<KeyboardInput>>,
+    mut turn_state: ResMut<State<TurnState>>,
+    mut auto_action: ResMut<AutoAction>,
+    mut prompt: ResMut<Prompt>,
+    mut ev_attacks: EventWriter<WantsToAttack>,
+    mut log: ResMut<GameLog>,
+    map_spec: Res<MapSpec>,
+    windows: Option<Res<Windows>>,
+    mouse_buttons: Option<Res<Input<MouseButton>>>,
+    camera_query: Query<&Transform, With<Camera>>,
+    mut player_query: Query<(Entity, &Point, &FieldOfView, &mut Energy), With<Player>>,
+    enemies_query: Query<(Entity, &Point), With<Enemy>>,
+) {
+    let mouse_buttons = match mouse_buttons {
+        Some(mouse_buttons) => mouse_buttons,
+        None => return,
+    };
+    let travel = mouse_buttons.just_pressed(MouseButton::Left);
+    let attack = mouse_buttons.just_pressed(MouseButton::Right);
+    if !travel && !attack {
+        return;
+    }
+
+    let clicked = match (windows, camera_query.single()) {
+        (Some(windows), Ok(camera_transform)) => windows
+            .get_primary()
+            .and_then(|window| cursor_map_pos(window, camera_transform, &map_spec)),
+        _ => None,
+    };
+    let clicked = match clicked.and_then(|pos| map_spec.try_idx(pos).map(|idx| (pos, idx))) {
+        Some(clicked) => clicked,
+        None => return,
+    };
+
+    let (player, &player_pos, fov, mut energy) = player_query.single_mut().unwrap();
+    *prompt = Prompt::None;
+
+    if attack {
+        let (clicked_pos, _) = clicked;
+        let victim = match enemies_query.iter().find(|(_, &pos)| {
+            pos == clicked_pos
+                && fov.visible_tiles.contains(&pos)
+                && map_spec.is_adjacent(player_pos, pos)
+        }) {
+            Some((victim, _)) => victim,
+            None => return,
+        };
+
+        ev_attacks.send(WantsToAttack {
+            attacker: player,
+            victim,
+            kind: AttackKind::Melee,
+        });
+        *auto_action = AutoAction::Idle;
+        energy.0 -= Energy::ACTION_COST;
+        turn_state.set(TurnState::PlayerTurn).unwrap();
+    } else {
+        let (clicked_pos, clicked_idx) = clicked;
+        let known_map = KnownMap {
+            map_spec: &map_spec,
+            visible_tiles: &fov.visible_tiles,
+            destination: clicked_idx,
+        };
+        if clicked_pos == player_pos
+            || !known_map.is_known(clicked_idx)
+            || !map_spec.can_enter_tile(clicked_pos)
+        {
+            return;
+        }
+
+        if enemies_query
+            .iter()
+            .any(|(_, pos)| fov.visible_tiles.contains(pos))
+        {
+            log.add("You can't travel with enemies in sight", Color::GRAY);
+            return;
+        }
+
+        let player_idx = map_spec.map_idx(player_pos.x, player_pos.y);
+        let path = a_star_search(player_idx, clicked_idx, &known_map);
+        if !path.success {
+            log.add("You can't find a way there", Color::GRAY);
+            return;
+        }
+
+        // The auto action system walks the path, the player's tile first
+        *auto_action = AutoAction::Travel(
+            path.steps
+                .iter()
+                .skip(1)
+                .map(|&idx| map_spec.index_to_point2d(idx))
+                .collect(),
+        );
+    }
+
+    // Keys pressed along with the click are dropped, the click being handled first
+    key_reader.iter(&key_events).for_each(drop);
+}
@@ -1,40 +1,85 @@
+use std::collections::HashSet;
+
 use crate::prelude::*;
 
 pub fn movement(
     mut map_spec: ResMut<MapSpec>,
     mut ev_movements: ResMut<Events<WantsToMove>>,
     mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    mut log: ResMut<GameLog>,
     mut tilemap_query: Query<&mut Tilemap>,
     player_query: Query<Entity, With<Player>>,
-    mut mover_query: Query<(&Point, &Render, &mut FieldOfView)>,
-    mut camera_query: Query<&mut Transform, With<Camera>>,
+    mut mover_query: Query<(
+        &Point,
+        &Render,
+        &mut FieldOfView,
+        Option<&mut StatusEffects>,
+        Option<&Name>,
+    )>,
+    traps_query: Query<(Entity, &Point, &Render, &Name, &InflictsEffects), With<Trap>>,
+    actors_query: Query<&Point, With<Health>>,
 ) {
     let mut tilemap = tilemap_query.single_mut().unwrap();
 
+    // Combat is already resolved, movers never end up on an actor's tile
+    let mut occupied: HashSet<Point> = actors_query.iter().copied().collect();
+
     for WantsToMove {
         entity,
         destination,
     } in ev_movements.drain()
     {
-        if map_spec.can_enter_tile(destination) {
-            let (&origin, render, mut fov) = mover_query.get_mut(entity).unwrap();
+        let (&origin, render, mut fov, mut status_effects, name) =
+            mover_query.get_mut(entity).unwrap();
 
+        // Confused movers stumble in a random direction half of the time
+        let confused = status_effects
+            .as_ref()
+            .is_some_and(|effects| effects.has(StatusEffect::Confusion));
+        let destination = if confused && rng.gen_range(0..2) == 0 {
+            let directions = map_spec.movement.directions();
+            origin + directions[rng.gen_range(0..directions.len())]
+        } else {
+            destination
+        };
+
+        if map_spec.can_enter_tile(destination) && !occupied.contains(&destination) {
+            occupied.remove(&origin);
+            occupied.insert(destination);
             commands.entity(entity).insert(destination);
-            move_sprite(&mut tilemap, origin, destination, &render);
+            move_sprite(&mut tilemap, &map_spec, origin, destination, render);
 
             let player = player_query.single().unwrap();
             if entity == player {
-                let mut camera_transform = camera_query.single_mut().unwrap();
-                let camera_translation = &mut camera_transform.translation;
-                camera_translation.x = (destination.x - CAMERA_OFFSET_X) as f32 * 32.;
-                camera_translation.y = (destination.y - CAMERA_OFFSET_Y) as f32 * 32.;
-
                 fov.visible_tiles.iter().for_each(|pos| {
-                    map_spec.revealed_tiles[map_idx(pos.x, pos.y)] = true;
+                    let idx = map_spec.map_idx(pos.x, pos.y);
+                    map_spec.revealed_tiles[idx] = true;
                 });
             }
 
             *fov = fov.clone_dirty();
+
+            if let Some((trap, &trap_pos, trap_render, trap_name, InflictsEffects(inflicted))) =
+                traps_query.iter().find(|(_, &pos, ..)| pos == destination)
+            {
+                let message = match name {
+                    Some(name) => format!("The {} triggers a {}", name.0, trap_name.0),
+                    None => format!("You trigger a {}", trap_name.0),
+                };
+                log.add(message, Color::ORANGE);
+
+                if let Some(status_effects) = status_effects.as_mut() {
+                    for &(effect, turns) in inflicted.iter() {
+                        status_effects.add(effect, turns);
+                    }
+                }
+
+                commands.entity(trap).despawn();
+                tilemap
+                    .clear_tile(map_spec.tilemap_pos(trap_pos), trap_render.sprite_order)
+                    .unwrap();
+            }
         }
     }
 }
@@ -1,72 +1,123 @@
+use std::collections::hash_map::Entry;
+
+use super::inventory_stacks;
 use crate::prelude::*;
 
 pub fn player_input(
-    mut key_evr: EventReader<KeyboardInput>,
+    key_events: Res<Events<KeyboardInput>>,
+    mut key_reader: ResMut<ManualEventReader<KeyboardInput>>,
+    input_map: Res<InputMap>,
     mut turn_state: ResMut<State<TurnState>>,
-    mut ev_movements: EventWriter<WantsToMove>,
-    mut ev_attacks: EventWriter<WantsToAttack>,
-    mut ev_item: EventWriter<ActivateItem>,
+    mut auto_action: ResMut<AutoAction>,
+    (mut ev_movements, mut ev_attacks, mut ev_item, mut ev_save_load): (
+        EventWriter<WantsToMove>,
+        EventWriter<WantsToAttack>,
+        EventWriter<ActivateItem>,
+        EventWriter<WantsToSaveOrLoad>,
+    ),
     mut commands: Commands,
-    player_query: Query<(Entity, &Point), With<Player>>,
+    map_spec: Res<MapSpec>,
+    mut player_query: Query<
+        (
+            Entity,
+            &Point,
+            &Health,
+            &FieldOfView,
+            &mut Energy,
+            &mut Equipment,
+        ),
+        With<Player>,
+    >,
     mut tilemap_query: Query<&mut Tilemap>,
-    items_query: Query<(Entity, &Point, &Render, Option<&Weapon>), With<Item>>,
-    carried_items_query: Query<(Entity, &Carried), With<Item>>,
-    carried_weapon_query: Query<(Entity, &Carried), With<Weapon>>,
+    items_query: Query<(Entity, &Point, &Render, &Name, Option<&Equippable>), With<Item>>,
+    mut log: ResMut<GameLog>,
+    carried_items_query: Query<
+        (
+            Entity,
+            &Carried,
+            &Render,
+            &Name,
+            Option<&RangedWeapon>,
+            Option<&Equippable>,
+        ),
+        With<Item>,
+    >,
     enemies_query: Query<(Entity, &Point), With<Enemy>>,
     font_handle: Res<Handle<Font>>,
+    mut prompt: ResMut<Prompt>,
 ) {
-    for ev in key_evr.iter().take(1) {
-        let (player, &player_pos) = player_query.single().unwrap();
+    for ev in key_reader.iter(&key_events).take(1) {
+        let (player, &player_pos, health, fov, mut energy, mut equipment) =
+            player_query.single_mut().unwrap();
+        let mut cost = Energy::ACTION_COST;
+
+        // The key following a drop command picks the item to drop
+        if *prompt == Prompt::Drop {
+            if ev.state != ElementState::Pressed {
+                return;
+            }
+            *prompt = Prompt::None;
+
+            let item = match ev.key_code.and_then(|key| input_map.player_action(key)) {
+                Some(PlayerAction::UseItem(n)) => nth_carried_item(n, player, &carried_items_query),
+                _ => None,
+            };
+            match item {
+                Some((item, render, name)) => {
+                    log.add(format!("You drop the {}", name.0), Color::WHITE);
+
+                    let mut tilemap = tilemap_query.single_mut().unwrap();
+                    drop_item(
+                        &mut commands,
+                        &mut tilemap,
+                        &map_spec,
+                        &mut equipment,
+                        item,
+                        render,
+                        player_pos,
+                    );
 
-        let delta = match (ev.state, ev.key_code) {
-            // movements
-            (ElementState::Pressed, Some(KeyCode::Up)) => Point::new(0, 1),
-            (ElementState::Pressed, Some(KeyCode::Left)) => Point::new(-1, 0),
-            (ElementState::Pressed, Some(KeyCode::Down)) => Point::new(0, -1),
-            (ElementState::Pressed, Some(KeyCode::Right)) => Point::new(1, 0),
+                    energy.0 -= cost;
+                    turn_state.set(TurnState::PlayerTurn).unwrap();
+                }
+                None => log.add("You drop nothing", Color::GRAY),
+            }
+            return;
+        }
 
+        let action = match (ev.state, ev.key_code) {
+            (ElementState::Pressed, Some(key)) => input_map.player_action(key),
+            // no key were pressed
+            _ => return,
+        };
+
+        let delta = match action {
             // pick up item
-            (ElementState::Pressed, Some(KeyCode::G)) => {
+            Some(PlayerAction::PickUp) => {
                 items_query
                     .iter()
-                    .filter(|(_, &item_pos, _, _)| item_pos == player_pos)
-                    .for_each(|(item, item_pos, item_render, weapon)| {
+                    .filter(|(_, &item_pos, ..)| item_pos == player_pos)
+                    .for_each(|(item, item_pos, item_render, name, equippable)| {
+                        log.add(format!("You pick up the {}", name.0), Color::WHITE);
+
                         commands.entity(item).remove::<Point>();
 
                         let mut tilemap = tilemap_query.single_mut().unwrap();
                         tilemap
-                            .clear_tile(
-                                (item_pos.x - CAMERA_OFFSET_X, item_pos.y - CAMERA_OFFSET_Y),
-                                item_render.sprite_order,
-                            )
+                            .clear_tile(map_spec.tilemap_pos(*item_pos), item_render.sprite_order)
                             .unwrap();
 
                         commands
                             .entity(item)
                             .insert(Carried(player))
-                            .insert_bundle(Text2dBundle {
-                                text: Text::with_section(
-                                    String::default(),
-                                    TextStyle {
-                                        font: font_handle.clone(),
-                                        font_size: 10.0,
-                                        color: Color::WHITE,
-                                    },
-                                    TextAlignment {
-                                        vertical: VerticalAlign::Bottom,
-                                        horizontal: HorizontalAlign::Right,
-                                    },
-                                ),
-                                ..Default::default()
-                            });
-
-                        if weapon.is_some() {
-                            carried_weapon_query
-                                .iter()
-                                .filter(|(_, &Carried(entity))| entity == player)
-                                .for_each(|(previous_weapon, _)| {
-                                    commands.entity(previous_weapon).despawn();
-                                });
+                            .insert_bundle(inventory_text(font_handle.clone()));
+
+                        // Equip the item straight away if its slot is free
+                        if let Some(&Equippable(slot)) = equippable {
+                            if let Entry::Vacant(entry) = equipment.0.entry(slot) {
+                                entry.insert(item);
+                                log.add(format!("You equip the {}", name.0), Color::WHITE);
+                            }
                         }
                     });
 
@@ -74,38 +125,109 @@ pub fn player_input(
             }
 
             // use item
-            (ElementState::Pressed, Some(KeyCode::Key1)) => {
-                use_item(0, player, &carried_items_query, &mut ev_item)
+            Some(PlayerAction::UseItem(n)) => {
+                use_item(n, player, &carried_items_query, &mut ev_item, &mut cost)
             }
-            (ElementState::Pressed, Some(KeyCode::Key2)) => {
-                use_item(1, player, &carried_items_query, &mut ev_item)
+
+            // aim a ranged weapon, the targeting system fires it
+            Some(PlayerAction::Fire) => {
+                let has_ranged_weapon = equipment
+                    .items()
+                    .any(|item| matches!(carried_items_query.get(item), Ok((.., Some(_), _))));
+                if has_ranged_weapon {
+                    turn_state.set(TurnState::Targeting).unwrap();
+                } else {
+                    log.add("You have no ranged weapon equipped", Color::GRAY);
+                }
+                return;
             }
-            (ElementState::Pressed, Some(KeyCode::Key3)) => {
-                use_item(2, player, &carried_items_query, &mut ev_item)
+
+            // drop item, the next key picks which one
+            Some(PlayerAction::Drop) => {
+                if nth_carried_item(0, player, &carried_items_query).is_some() {
+                    *prompt = Prompt::Drop;
+                    log.add(
+                        format!(
+                            "Drop which item? ({}-{})",
+                            input_map.key_label(PlayerAction::UseItem(0)),
+                            input_map.key_label(PlayerAction::UseItem(8))
+                        ),
+                        Color::GRAY,
+                    );
+                } else {
+                    log.add("You have nothing to drop", Color::GRAY);
+                }
+                return;
             }
-            (ElementState::Pressed, Some(KeyCode::Key4)) => {
-                use_item(3, player, &carried_items_query, &mut ev_item)
+
+            // inventory screen
+            Some(PlayerAction::Inventory) => {
+                turn_state.set(TurnState::Inventory).unwrap();
+                return;
             }
-            (ElementState::Pressed, Some(KeyCode::Key5)) => {
-                use_item(4, player, &carried_items_query, &mut ev_item)
+
+            // message log
+            Some(PlayerAction::MessageLog) => {
+                turn_state.set(TurnState::ShowingLog).unwrap();
+                return;
             }
-            (ElementState::Pressed, Some(KeyCode::Key6)) => {
-                use_item(5, player, &carried_items_query, &mut ev_item)
+
+            // save/load, done by their own systems
+            Some(PlayerAction::Save) => {
+                ev_save_load.send(WantsToSaveOrLoad::Save);
+                return;
             }
-            (ElementState::Pressed, Some(KeyCode::Key7)) => {
-                use_item(6, player, &carried_items_query, &mut ev_item)
+            Some(PlayerAction::Load) => {
+                ev_save_load.send(WantsToSaveOrLoad::Load);
+                return;
             }
-            (ElementState::Pressed, Some(KeyCode::Key8)) => {
-                use_item(7, player, &carried_items_query, &mut ev_item)
+
+            // pass the turn
+            Some(PlayerAction::Wait) => Point::zero(),
+
+            // rest, the auto action system then waits every turn
+            Some(PlayerAction::Rest) => {
+                if enemies_query
+                    .iter()
+                    .any(|(_, pos)| fov.visible_tiles.contains(pos))
+                {
+                    log.add("You can't rest with enemies in sight", Color::GRAY);
+                } else if health.current >= health.max {
+                    log.add("You are already fully rested", Color::GRAY);
+                } else {
+                    log.add("You rest", Color::WHITE);
+                    *auto_action = AutoAction::Rest;
+                }
+                return;
             }
-            (ElementState::Pressed, Some(KeyCode::Key9)) => {
-                use_item(8, player, &carried_items_query, &mut ev_item)
+
+            // explore, the auto action system then steps every turn
+            Some(PlayerAction::Explore) => {
+                if enemies_query
+                    .iter()
+                    .any(|(_, pos)| fov.visible_tiles.contains(pos))
+                {
+                    log.add("You can't explore with enemies in sight", Color::GRAY);
+                } else {
+                    *auto_action = AutoAction::Explore;
+                }
+                return;
             }
 
+            // movements, diagonal ones depending on the movement of the game
+            Some(action) => match action.delta() {
+                Some(delta) if map_spec.is_adjacent(player_pos, player_pos + delta) => delta,
+                _ => {
+                    log.add(
+                        "You can only move in the four cardinal directions",
+                        Color::GRAY,
+                    );
+                    return;
+                }
+            },
+
             // ignore other keys
-            (ElementState::Pressed, Some(_)) => Point::zero(),
-            // no key were pressed
-            _ => return,
+            None => return,
         };
 
         // Move or attack
@@ -122,6 +244,7 @@ pub fn player_input(
                     ev_attacks.send(WantsToAttack {
                         attacker: player,
                         victim: enemy,
+                        kind: AttackKind::Melee,
                     });
                 });
 
@@ -133,29 +256,100 @@ pub fn player_input(
             }
         }
 
+        energy.0 -= cost;
         turn_state.set(TurnState::PlayerTurn).unwrap();
     }
 }
 
+/// Puts a carried item back on the map at `position`, unequipping it first.
+pub fn drop_item(
+    commands: &mut Commands,
+    tilemap: &mut Tilemap,
+    map_spec: &MapSpec,
+    equipment: &mut Equipment,
+    item: Entity,
+    render: &Render,
+    position: Point,
+) {
+    if let Some(slot) = equipment.slot_of(item) {
+        equipment.0.remove(&slot);
+    }
+
+    commands
+        .entity(item)
+        .remove::<Carried>()
+        .remove_bundle::<Text2dBundle>()
+        .insert(position);
+
+    tilemap
+        .insert_tile(Tile {
+            point: map_spec.tilemap_pos(position),
+            sprite_index: render.sprite_index,
+            sprite_order: render.sprite_order,
+            tint: Color::WHITE,
+        })
+        .unwrap();
+}
+
+/// First item of the `n`th stack carried by `player`, in the order of the inventory HUD.
+fn nth_carried_item<'a>(
+    n: usize,
+    player: Entity,
+    carried_items_query: &'a Query<
+        (
+            Entity,
+            &Carried,
+            &Render,
+            &Name,
+            Option<&RangedWeapon>,
+            Option<&Equippable>,
+        ),
+        With<Item>,
+    >,
+) -> Option<(Entity, &'a Render, &'a Name)> {
+    let stacks = inventory_stacks(
+        carried_items_query
+            .iter()
+            .filter(|(_, carried, ..)| carried.0 == player)
+            .map(|(item, _, _, name, _, equippable)| (item, name, equippable.is_none())),
+    );
+    let item = *stacks.get(n)?.first()?;
+    carried_items_query
+        .get(item)
+        .ok()
+        .map(|(item, _, render, name, ..)| (item, render, name))
+}
+
 fn use_item(
     n: usize,
     player: Entity,
-    carried_items_query: &Query<(Entity, &Carried), With<Item>>,
+    carried_items_query: &Query<
+        (
+            Entity,
+            &Carried,
+            &Render,
+            &Name,
+            Option<&RangedWeapon>,
+            Option<&Equippable>,
+        ),
+        With<Item>,
+    >,
     ev_item: &mut EventWriter<ActivateItem>,
+    cost: &mut i32,
 ) -> Point {
-    let item = carried_items_query
-        .iter()
-        .filter(|(_, carried)| carried.0 == player)
-        .enumerate()
-        .filter(|&(item_idx, (_, _))| item_idx == n)
-        .find_map(|(_, (item, _))| Some(item));
-
-    if let Some(item) = item {
+    if let Some((item, ..)) = nth_carried_item(n, player, carried_items_query) {
         ev_item.send(ActivateItem {
             used_by: player,
             item,
         });
+        *cost = Energy::QUICK_ACTION_COST;
     }
 
     Point::zero()
 }
+
+/// Forgets the question asked to the player when anything else ends their input, e.g. an
+/// auto action or a mouse attack.
+pub fn clear_prompt(mut prompt: ResMut<Prompt>) {
+    *prompt = Prompt::None;
+}
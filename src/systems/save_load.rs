@@ -0,0 +1,83 @@
+use crate::prelude::*;
+
+pub fn save_game(world: &mut World) {
+    // Requests are sent by player_input, which reads the keys. Running last, after load_game
+    // has seen them, this system clears them.
+    let requested = world
+        .get_resource_mut::<Events<WantsToSaveOrLoad>>()
+        .is_some_and(|mut ev_save_load| {
+            ev_save_load.drain().any(|ev| ev == WantsToSaveOrLoad::Save)
+        });
+    if !requested {
+        return;
+    }
+
+    match SaveGame::capture(world).save(SAVE_FILE) {
+        Ok(()) => println!("Game saved to {}", SAVE_FILE),
+        Err(e) => println!("Warning: unable to save game to {}: {}", SAVE_FILE, e),
+    }
+}
+
+pub fn load_game(
+    mut ev_save_load: EventReader<WantsToSaveOrLoad>,
+    input_map: Res<InputMap>,
+    mut auto_action: ResMut<AutoAction>,
+    font_handle: Res<Handle<Font>>,
+    texture_atlas_handle: Res<Handle<TextureAtlas>>,
+    mut commands: Commands,
+    mut turn_state: ResMut<State<TurnState>>,
+    entities_query: Query<
+        Entity,
+        Or<(
+            With<Player>,
+            With<Item>,
+            With<Enemy>,
+            With<Trap>,
+            With<Hud>,
+            With<Tilemap>,
+        )>,
+    >,
+) {
+    if !ev_save_load.iter().any(|&ev| ev == WantsToSaveOrLoad::Load) {
+        return;
+    }
+
+    let save_game = match SaveGame::load(SAVE_FILE) {
+        Ok(save_game) => save_game,
+        Err(e) => {
+            println!("Warning: unable to load game from {}: {}", SAVE_FILE, e);
+            return;
+        }
+    };
+
+    // Remove current game
+
+    *auto_action = AutoAction::Idle;
+
+    for e in entities_query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+
+    // Rebuild saved game
+
+    let mut tilemap = build_tilemap(texture_atlas_handle.clone(), &save_game.map_spec);
+    save_game.restore(&mut commands, &mut tilemap, &font_handle);
+
+    spawn_hud(&mut commands, font_handle.clone(), &input_map);
+    spawn_tilemap(&mut commands, tilemap);
+
+    let SaveGame {
+        turn_state: saved_state,
+        map_spec,
+        dungeon,
+        ..
+    } = save_game;
+    // Levels built from now on keep the movement of the saved game
+    commands.insert_resource(map_spec.movement);
+    commands.insert_resource(map_spec);
+    commands.insert_resource(dungeon);
+
+    if turn_state.current() != &saved_state {
+        turn_state.set(saved_state).unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+use crate::prelude::*;
+
+/// Spawn points of the map built at startup, consumed by `setup_entities`.
+pub struct LevelSpawns {
+    pub player_start: Point,
+    pub monster_spawns: Vec<Point>,
+    pub prefab_spawns: Vec<(Point, PrefabSpawn)>,
+}
+
+pub fn setup_assets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    // Setup textures
+
+    let texture_handle = asset_server.load("dungeonfont.png");
+    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(32.0, 32.0), 16, 16);
+
+    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    commands.insert_resource(texture_atlas_handle);
+
+    // Setup font
+
+    let font_handle: Handle<Font> = asset_server.load("BigBlue_Terminal_437TT.TTF");
+    commands.insert_resource(font_handle);
+
+    // Spawn camera
+
+    spawn_camera(&mut commands);
+}
+
+pub fn setup_map(
+    mut commands: Commands,
+    texture_atlas_handle: Res<Handle<TextureAtlas>>,
+    mut rng: ResMut<GameRng>,
+    movement: Res<Movement>,
+    prefabs: Res<Prefabs>,
+) {
+    let (tilemap, map_builder) = make_tilemap(
+        texture_atlas_handle.clone(),
+        0,
+        *movement,
+        &prefabs,
+        &mut rng,
+    );
+    let MapBuilder {
+        player_start,
+        monster_spawns,
+        prefab_spawns,
+        map_spec,
+        ..
+    } = map_builder;
+
+    commands.insert_resource(map_spec);
+    commands.insert_resource(LevelSpawns {
+        player_start,
+        monster_spawns,
+        prefab_spawns,
+    });
+    spawn_tilemap(&mut commands, tilemap);
+}
+
+pub fn setup_entities(
+    mut commands: Commands,
+    level_spawns: Res<LevelSpawns>,
+    map_spec: Res<MapSpec>,
+    mut tilemap_query: Query<&mut Tilemap>,
+    mut rng: ResMut<GameRng>,
+    templates: Res<Templates>,
+) {
+    let mut tilemap = tilemap_query.single_mut().unwrap();
+
+    spawn_player(
+        &mut commands,
+        level_spawns.player_start,
+        &mut tilemap,
+        &map_spec,
+    );
+    spawn_level(
+        &templates,
+        0,
+        &mut commands,
+        &level_spawns.monster_spawns,
+        &level_spawns.prefab_spawns,
+        &mut tilemap,
+        &map_spec,
+        &mut rng,
+    );
+
+    commands.remove_resource::<LevelSpawns>();
+}
+
+pub fn setup_hud(mut commands: Commands, font_handle: Res<Handle<Font>>, input_map: Res<InputMap>) {
+    spawn_hud(&mut commands, font_handle.clone(), &input_map);
+    spawn_end_game_screens(&mut commands, font_handle.clone(), &input_map);
+}
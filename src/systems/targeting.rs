@@ -0,0 +1,162 @@
+use super::{cursor_map_pos, drop_item};
+use crate::prelude::*;
+
+/// How far carried items can be thrown.
+const THROW_RANGE: i32 = 5;
+
+pub fn targeting(
+    key_events: Res<Events<KeyboardInput>>,
+    input_map: Res<InputMap>,
+    mut key_reader: ResMut<ManualEventReader<KeyboardInput>>,
+    mut turn_state: ResMut<State<TurnState>>,
+    mut targeting: ResMut<Targeting>,
+    mut ev_attacks: EventWriter<WantsToAttack>,
+    mut log: ResMut<GameLog>,
+    mut commands: Commands,
+    map_spec: Res<MapSpec>,
+    windows: Option<Res<Windows>>,
+    mouse_buttons: Option<Res<Input<MouseButton>>>,
+    camera_query: Query<&Transform, With<Camera>>,
+    mut player_query: Query<
+        (Entity, &Point, &FieldOfView, &mut Equipment, &mut Energy),
+        With<Player>,
+    >,
+    mut tilemap_query: Query<&mut Tilemap>,
+    items_query: Query<(&Render, Option<&RangedWeapon>), With<Item>>,
+    enemies_query: Query<(Entity, &Point), With<Enemy>>,
+) {
+    let (player, &player_pos, fov, mut equipment, mut energy) = player_query.single_mut().unwrap();
+
+    let range = match targeting.thrown {
+        Some(_) => THROW_RANGE,
+        None => equipment
+            .items()
+            .filter_map(|item| items_query.get(item).ok())
+            .filter_map(|(_, ranged)| ranged)
+            .map(|ranged| ranged.range)
+            .max()
+            .unwrap_or(0),
+    };
+
+    // Enemies in sight, within range and line of fire, the closest first
+    let mut targets = enemies_query
+        .iter()
+        .filter(|(_, pos)| fov.visible_tiles.contains(pos))
+        .map(|(enemy, &pos)| {
+            let distance = DistanceAlg::Pythagoras.distance2d(player_pos, pos);
+            (enemy, pos, distance)
+        })
+        .filter(|&(_, pos, distance)| {
+            distance <= range as f32 && map_spec.has_line_of_fire(player_pos, pos)
+        })
+        .collect::<Vec<_>>();
+    targets.sort_by(|(_, _, d1), (_, _, d2)| d1.partial_cmp(d2).unwrap());
+
+    if targets.is_empty() {
+        log.add("There is no target in range", Color::GRAY);
+        *targeting = Targeting::default();
+        turn_state.set(TurnState::AwaitingInput).unwrap();
+        return;
+    }
+
+    // Follow the mouse, but only once it moves so that it doesn't hide the default target
+
+    let mouse = match (windows, camera_query.single()) {
+        (Some(windows), Ok(camera_transform)) => windows
+            .get_primary()
+            .and_then(|window| cursor_map_pos(window, camera_transform, &map_spec)),
+        _ => None,
+    };
+
+    if targeting.cursor.is_none() {
+        targeting.cursor = Some(targets[0].1);
+    } else if mouse.is_some() && mouse != targeting.mouse {
+        targeting.cursor = mouse;
+    }
+    targeting.mouse = mouse;
+
+    // Handle user input
+
+    let mut fire = mouse_buttons.is_some_and(|buttons| buttons.just_pressed(MouseButton::Left));
+    let current = targets
+        .iter()
+        .position(|&(_, pos, _)| Some(pos) == targeting.cursor);
+
+    for ev in key_reader.iter(&key_events).take(1) {
+        let action = input_map.menu_action(
+            ev,
+            &[
+                PlayerAction::NextTarget,
+                PlayerAction::MoveEast,
+                PlayerAction::MoveNorth,
+                PlayerAction::MoveWest,
+                PlayerAction::MoveSouth,
+                PlayerAction::Confirm,
+                PlayerAction::Fire,
+                PlayerAction::Cancel,
+            ],
+        );
+
+        match action {
+            Some(PlayerAction::NextTarget)
+            | Some(PlayerAction::MoveEast)
+            | Some(PlayerAction::MoveNorth) => {
+                let next = current.map_or(0, |idx| (idx + 1) % targets.len());
+                targeting.cursor = Some(targets[next].1);
+            }
+            Some(PlayerAction::MoveWest) | Some(PlayerAction::MoveSouth) => {
+                let previous = current.map_or(0, |idx| (idx + targets.len() - 1) % targets.len());
+                targeting.cursor = Some(targets[previous].1);
+            }
+            Some(PlayerAction::Confirm) | Some(PlayerAction::Fire) => fire = true,
+            Some(PlayerAction::Cancel) => {
+                *targeting = Targeting::default();
+                turn_state.set(TurnState::AwaitingInput).unwrap();
+                return;
+            }
+            _ => (),
+        }
+    }
+
+    if !fire {
+        return;
+    }
+
+    match targets
+        .iter()
+        .find(|&&(_, pos, _)| Some(pos) == targeting.cursor)
+    {
+        Some(&(victim, victim_pos, distance)) => {
+            let distance = distance.round() as i32;
+            let kind = match targeting.thrown {
+                Some(item) => {
+                    // The thrown item lands under its victim, hit or miss
+                    if let Ok((render, _)) = items_query.get(item) {
+                        let mut tilemap = tilemap_query.single_mut().unwrap();
+                        drop_item(
+                            &mut commands,
+                            &mut tilemap,
+                            &map_spec,
+                            &mut equipment,
+                            item,
+                            render,
+                            victim_pos,
+                        );
+                    }
+                    AttackKind::Thrown { item, distance }
+                }
+                None => AttackKind::Ranged { distance },
+            };
+            ev_attacks.send(WantsToAttack {
+                attacker: player,
+                victim,
+                kind,
+            });
+
+            energy.0 -= Energy::ACTION_COST;
+            *targeting = Targeting::default();
+            turn_state.set(TurnState::PlayerTurn).unwrap();
+        }
+        None => log.add("You can't fire there", Color::GRAY),
+    }
+}
@@ -3,6 +3,7 @@ use crate::prelude::*;
 
 pub fn tooltips(
     windows: Res<Windows>,
+    map_spec: Res<MapSpec>,
     mut commands: Commands,
     font_handle: Res<Handle<Font>>,
     camera_query: Query<&Transform, With<Camera>>,
@@ -11,16 +12,9 @@ pub fn tooltips(
 ) {
     let window = windows.get_primary().unwrap();
 
-    if let Some(pos) = window.cursor_position() {
-        let window_size = Vec2::new(window.width() as f32, window.height() as f32);
-        let p = pos - window_size / 2.0;
-        let camera_transform = camera_query.single().unwrap();
-        let world_pos = camera_transform.compute_matrix() * p.extend(0.0).extend(1.0);
-        let map_pos = Point {
-            x: (world_pos.x / 32.).floor() as i32 + CAMERA_OFFSET_X,
-            y: (world_pos.y / 32.).floor() as i32 + CAMERA_OFFSET_Y,
-        };
+    let camera_transform = camera_query.single().unwrap();
 
+    if let Some(map_pos) = cursor_map_pos(window, camera_transform, &map_spec) {
         let player_fov = match player_query.single() {
             Ok(player_fov) => player_fov,
             Err(_) => return,
@@ -62,13 +56,32 @@ pub fn tooltips(
     }
 }
 
+/// Map point under the mouse cursor, if it is within the window.
+pub fn cursor_map_pos(
+    window: &Window,
+    camera_transform: &Transform,
+    map_spec: &MapSpec,
+) -> Option<Point> {
+    let pos = window.cursor_position()?;
+    let window_size = Vec2::new(window.width() as f32, window.height() as f32);
+    let p = pos - window_size / 2.0;
+    let world_pos = camera_transform.compute_matrix() * p.extend(0.0).extend(1.0);
+    let offset = map_spec.tilemap_offset();
+    Some(Point {
+        x: (world_pos.x / 32.).floor() as i32 + offset.x,
+        y: (world_pos.y / 32.).floor() as i32 + offset.y,
+    })
+}
+
 pub fn tooltips_display(
+    map_spec: Res<MapSpec>,
     mut tooltips_query: Query<(&mut Transform, &Text2dSize, &Point), With<TooltipText>>,
 ) {
     for (mut transform, size, pos) in tooltips_query.iter_mut() {
         let tooltip_height = size.size.height;
-        transform.translation.x = (pos.x - CAMERA_OFFSET_X) as f32 * 32.;
-        transform.translation.y = (pos.y - CAMERA_OFFSET_Y + 1) as f32 * 32. - tooltip_height;
+        let (x, y) = map_spec.tilemap_pos(*pos);
+        transform.translation.x = x as f32 * 32.;
+        transform.translation.y = (y + 1) as f32 * 32. - tooltip_height;
         transform.translation.z = 999.0;
     }
 }
@@ -4,17 +4,24 @@ pub fn use_items(
     mut ev_items: ResMut<Events<ActivateItem>>,
     mut commands: Commands,
     mut map_spec: ResMut<MapSpec>,
+    mut log: ResMut<GameLog>,
     items_query: Query<
         (
             Entity,
+            &Name,
             Option<&ProvidesHealing>,
             Option<&ProvidesDungeonMap>,
+            Option<&ProvidesEffects>,
+            Option<&Equippable>,
         ),
         With<Item>,
     >,
     mut healed_query: Query<(Entity, &mut Health)>,
+    mut effects_query: Query<&mut StatusEffects>,
+    mut equipment_query: Query<&mut Equipment>,
 ) {
     let mut healing_to_apply = Vec::<(Entity, i32)>::new();
+    let mut effects_to_apply = Vec::<(Entity, StatusEffect, i32)>::new();
     ev_items
         .drain()
         .filter_map(|ActivateItem { used_by, item }| {
@@ -23,21 +30,60 @@ pub fn use_items(
                 .ok()
                 .map(|item_components| (used_by, item_components))
         })
-        .for_each(|(used_by, (item, healing, dungeon_map))| {
-            if let Some(healing) = healing {
-                healing_to_apply.push((used_by, healing.amount));
-            }
+        .for_each(
+            |(used_by, (item, name, healing, dungeon_map, effects, equippable))| {
+                // Equippable items are toggled in and out of their slot instead of used up
+                if let Some(&Equippable(slot)) = equippable {
+                    if let Ok(mut equipment) = equipment_query.get_mut(used_by) {
+                        if equipment.0.get(&slot) == Some(&item) {
+                            equipment.0.remove(&slot);
+                            log.add(format!("You unequip the {}", name.0), Color::WHITE);
+                        } else {
+                            equipment.0.insert(slot, item);
+                            log.add(format!("You equip the {}", name.0), Color::WHITE);
+                        }
+                    }
+                    return;
+                }
 
-            if let Some(_dungeon_map) = dungeon_map {
-                map_spec.revealed_tiles.iter_mut().for_each(|t| *t = true);
-            }
+                log.add(format!("You use the {}", name.0), Color::WHITE);
 
-            commands.entity(item).despawn();
-        });
+                if let Some(healing) = healing {
+                    healing_to_apply.push((used_by, healing.amount));
+                    log.add(
+                        format!("You recover {} health", healing.amount),
+                        Color::GREEN,
+                    );
+                }
+
+                if let Some(_dungeon_map) = dungeon_map {
+                    map_spec.revealed_tiles.iter_mut().for_each(|t| *t = true);
+                    log.add("The layout of the level is revealed", Color::GREEN);
+                }
+
+                if let Some(ProvidesEffects(effects)) = effects {
+                    for &(effect, turns) in effects.iter() {
+                        effects_to_apply.push((used_by, effect, turns));
+                        log.add(
+                            format!("You are {}", effect.name().to_lowercase()),
+                            effect.color(),
+                        );
+                    }
+                }
+
+                commands.entity(item).despawn();
+            },
+        );
 
     for (entity, heal) in healing_to_apply.into_iter() {
         if let Ok((_, mut health)) = healed_query.get_mut(entity) {
             health.current = i32::min(health.max, health.current + heal);
         }
     }
+
+    for (entity, effect, turns) in effects_to_apply.into_iter() {
+        if let Ok(mut status_effects) = effects_query.get_mut(entity) {
+            status_effects.add(effect, turns);
+        }
+    }
 }
@@ -0,0 +1,58 @@
+use bevy::app::AppExit;
+
+use dungeoncrawl::prelude::*;
+use dungeoncrawl::{HeadlessPlugin, MapPlugin, SpawnerPlugin, TurnPlugin};
+
+const MAX_UPDATES: usize = 20_000;
+
+/// Builds a headless game the way `main` does, without a window or renderer.
+fn headless_app(seed: u64, script: &str, games: usize) -> App {
+    let script = ScriptedInput::parse(script).unwrap();
+
+    let mut app = App::build();
+    app.insert_resource(GameRng::new(seed))
+        .add_plugins(MinimalPlugins)
+        .insert_resource(Simulation::new(script, games))
+        .add_plugin(HeadlessPlugin)
+        .add_plugin(TurnPlugin)
+        .add_plugin(MapPlugin)
+        .add_plugin(SpawnerPlugin);
+    app.app
+}
+
+/// Updates the app until the simulation asks to exit, returns whether it did.
+fn run_until_exit(app: &mut App) -> bool {
+    let mut exit_reader = ManualEventReader::<AppExit>::default();
+    for _ in 0..MAX_UPDATES {
+        app.update();
+        let exit_events = app.world.get_resource::<Events<AppExit>>().unwrap();
+        if exit_reader.iter(exit_events).next().is_some() {
+            return true;
+        }
+    }
+    false
+}
+
+#[test]
+fn scripted_game_runs_to_the_end() {
+    let mut app = headless_app(1234, "Up Up Left Left G Down Right Key1 Space Down Down", 1);
+
+    assert!(run_until_exit(&mut app));
+    assert_eq!(app.world.get_resource::<Simulation>().unwrap().played(), 1);
+}
+
+#[test]
+fn scripts_can_open_the_log_and_inventory() {
+    let mut app = headless_app(1234, "Up L Down I Escape L Left I", 2);
+
+    assert!(run_until_exit(&mut app));
+    assert_eq!(app.world.get_resource::<Simulation>().unwrap().played(), 2);
+}
+
+#[test]
+fn random_games_run_to_the_end() {
+    let mut app = headless_app(42, "", 2);
+
+    assert!(run_until_exit(&mut app));
+    assert_eq!(app.world.get_resource::<Simulation>().unwrap().played(), 2);
+}